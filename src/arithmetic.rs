@@ -0,0 +1,513 @@
+use std::{cell::RefCell, collections::HashMap, env};
+
+use crate::{Result, utils::is_main_thread};
+
+// 变量的值本身也可以是表达式，限制递归深度避免 a=a 这样的死循环
+const MAX_RECURSION_DEPTH: usize = 32;
+
+// tokenize 时 $((expr)) 只用这两个字符标记出来，等到命令执行前再求值
+pub const ARITHMETIC_START: char = '\u{1}';
+pub const ARITHMETIC_END: char = '\u{2}';
+
+thread_local! {
+    // 管道中的命令运行在单独的线程里，不能修改环境变量，赋值只在该线程内可见，和 bash 的子 shell 一样
+    static THREAD_VARIABLES: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+// 按照长度从长到短排列，保证最长匹配
+const OPERATORS: [&str; 39] = [
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "!", "~", "&", "^", "|",
+    "?", ":", "=", "(", ")", ",",
+];
+
+// 支持 0x 十六进制、0 开头的八进制以及 base#digits 的写法
+fn parse_number(literal: &str) -> std::result::Result<i64, String> {
+    let invalid = || format!("{}: value too great for base", literal);
+    let (base, digits) = if let Some((base, digits)) = literal.split_once('#') {
+        let base: u32 = base.parse().map_err(|_| invalid())?;
+        if !(2..=64).contains(&base) {
+            return Err(format!("{}: invalid arithmetic base", literal));
+        }
+        (base, digits)
+    } else if let Some(hex) = literal
+        .strip_prefix("0x")
+        .or_else(|| literal.strip_prefix("0X"))
+    {
+        (16, hex)
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        (8, &literal[1..])
+    } else {
+        (10, literal)
+    };
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        // base 大于 36 时，小写字母、大写字母、@ 和 _ 依次表示 10 到 63
+        let digit = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(invalid()),
+        };
+        if digit >= base {
+            return Err(invalid());
+        }
+        value = value.wrapping_mul(base as i64).wrapping_add(digit as i64);
+    }
+    Ok(value)
+}
+
+fn lex(expr: &str) -> std::result::Result<Vec<Token>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c.is_whitespace() {
+            idx += 1;
+        } else if c.is_ascii_digit() {
+            let start = idx;
+            while idx < chars.len()
+                && (chars[idx].is_ascii_alphanumeric() || "#@_".contains(chars[idx]))
+            {
+                idx += 1;
+            }
+            let literal: String = chars[start..idx].iter().collect();
+            tokens.push(Token::Number(parse_number(&literal)?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = idx;
+            while idx < chars.len() && (chars[idx].is_ascii_alphanumeric() || chars[idx] == '_') {
+                idx += 1;
+            }
+            tokens.push(Token::Ident(chars[start..idx].iter().collect()));
+        } else if c == '$' {
+            // $name 和 name 等价
+            idx += 1;
+        } else {
+            let rest: String = chars[idx..chars.len().min(idx + 3)].iter().collect();
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                return Err(format!(
+                    "syntax error: invalid arithmetic operator (error token is \"{}\")",
+                    chars[idx..].iter().collect::<String>()
+                ));
+            };
+            tokens.push(Token::Op(op));
+            idx += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(i64),
+    Variable(String),
+    Unary(&'static str, Box<Expr>),
+    // ++x --x x++ x--，bool 表示是否为后缀形式
+    Increment(String, i64, bool),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    // 复合赋值时保存对应的二元运算符，比如 += 对应 +
+    Assign(String, Option<&'static str>, Box<Expr>),
+    Comma(Box<Expr>, Box<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+// 二元运算符的优先级，数字越大优先级越高
+fn binary_precedence(op: &str) -> Option<u8> {
+    let precedence = match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | ">" | "<=" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    };
+    Some(precedence)
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect_op(&mut self, op: &str) -> std::result::Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("syntax error: `{}' expected", op))
+        }
+    }
+
+    fn comma(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.assignment()?;
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            expr = Expr::Comma(Box::new(expr), Box::new(self.assignment()?));
+        }
+        Ok(expr)
+    }
+
+    fn assignment(&mut self) -> std::result::Result<Expr, String> {
+        if let (Some(Token::Ident(name)), Some(Token::Op(op))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+            && op.ends_with('=')
+            && !matches!(*op, "==" | "!=" | "<=" | ">=")
+        {
+            let name = name.clone();
+            let binary_op = match *op {
+                "=" => None,
+                op => OPERATORS
+                    .iter()
+                    .copied()
+                    .find(|candidate| *candidate == &op[..op.len() - 1]),
+            };
+            self.pos += 2;
+            let value = self.assignment()?;
+            return Ok(Expr::Assign(name, binary_op, Box::new(value)));
+        }
+        self.ternary()
+    }
+
+    fn ternary(&mut self) -> std::result::Result<Expr, String> {
+        let condition = self.binary(1)?;
+        if self.peek_op() != Some("?") {
+            return Ok(condition);
+        }
+        self.pos += 1;
+        let then_expr = self.assignment()?;
+        self.expect_op(":")?;
+        let else_expr = self.assignment()?;
+        Ok(Expr::Ternary(
+            Box::new(condition),
+            Box::new(then_expr),
+            Box::new(else_expr),
+        ))
+    }
+
+    // 优先级爬升，** 为右结合，其余为左结合
+    fn binary(&mut self, min_precedence: u8) -> std::result::Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.peek_op()
+            && let Some(precedence) = binary_precedence(op)
+            && precedence >= min_precedence
+        {
+            self.pos += 1;
+            let next_precedence = if op == "**" {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs = self.binary(next_precedence)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> std::result::Result<Expr, String> {
+        match self.peek_op() {
+            Some(op @ ("++" | "--")) => {
+                self.pos += 1;
+                let Some(Token::Ident(name)) = self.tokens.get(self.pos).cloned() else {
+                    return Err(format!("syntax error: operand expected after `{}'", op));
+                };
+                self.pos += 1;
+                let delta = if op == "++" { 1 } else { -1 };
+                Ok(Expr::Increment(name, delta, false))
+            }
+            Some(op @ ("!" | "~" | "-" | "+")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> std::result::Result<Expr, String> {
+        let expr = self.primary()?;
+        if let Expr::Variable(name) = &expr
+            && let Some(op @ ("++" | "--")) = self.peek_op()
+        {
+            self.pos += 1;
+            let delta = if op == "++" { 1 } else { -1 };
+            return Ok(Expr::Increment(name.clone(), delta, true));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> std::result::Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Ident(name)) => Ok(Expr::Variable(name)),
+            Some(Token::Op("(")) => {
+                let expr = self.comma()?;
+                self.expect_op(")")?;
+                Ok(expr)
+            }
+            Some(Token::Op(op)) => Err(format!(
+                "syntax error: operand expected (error token is \"{}\")",
+                op
+            )),
+            None => Err("syntax error: operand expected".to_string()),
+        }
+    }
+}
+
+fn read_variable(name: &str, depth: usize) -> std::result::Result<i64, String> {
+    let value = THREAD_VARIABLES
+        .with(|variables| variables.borrow().get(name).cloned())
+        .or_else(|| env::var(name).ok())
+        .unwrap_or_default();
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(0);
+    }
+    if let Ok(number) = parse_number(value) {
+        return Ok(number);
+    }
+    if depth >= MAX_RECURSION_DEPTH {
+        return Err(format!("{}: expression recursion level exceeded", name));
+    }
+    evaluate_with_depth(value, depth + 1)
+}
+
+fn write_variable(name: &str, value: i64) {
+    if is_main_thread() {
+        unsafe { env::set_var(name, value.to_string()) };
+    } else {
+        THREAD_VARIABLES.with(|variables| {
+            variables
+                .borrow_mut()
+                .insert(name.to_string(), value.to_string())
+        });
+    }
+}
+
+fn apply_binary(op: &str, lhs: i64, rhs: i64) -> std::result::Result<i64, String> {
+    let value = match op {
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" if rhs == 0 => return Err("division by 0".to_string()),
+        "/" => lhs.wrapping_div(rhs),
+        "%" => lhs.wrapping_rem(rhs),
+        "**" if rhs < 0 => return Err("exponent less than 0".to_string()),
+        "**" => lhs.wrapping_pow(rhs.min(u32::MAX as i64) as u32),
+        "<<" => lhs.wrapping_shl(rhs as u32),
+        ">>" => lhs.wrapping_shr(rhs as u32),
+        "&" => lhs & rhs,
+        "|" => lhs | rhs,
+        "^" => lhs ^ rhs,
+        "<" => (lhs < rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        _ => unreachable!(),
+    };
+    Ok(value)
+}
+
+fn eval(expr: &Expr, depth: usize) -> std::result::Result<i64, String> {
+    match expr {
+        Expr::Number(value) => Ok(*value),
+        Expr::Variable(name) => read_variable(name, depth),
+        Expr::Unary(op, operand) => {
+            let value = eval(operand, depth)?;
+            Ok(match *op {
+                "!" => (value == 0) as i64,
+                "~" => !value,
+                "-" => value.wrapping_neg(),
+                _ => value,
+            })
+        }
+        Expr::Increment(name, delta, postfix) => {
+            let old = read_variable(name, depth)?;
+            let new = old.wrapping_add(*delta);
+            write_variable(name, new);
+            Ok(if *postfix { old } else { new })
+        }
+        // && 和 || 需要短路求值，右侧可能包含赋值
+        Expr::Binary("&&", lhs, rhs) => {
+            Ok((eval(lhs, depth)? != 0 && eval(rhs, depth)? != 0) as i64)
+        }
+        Expr::Binary("||", lhs, rhs) => {
+            Ok((eval(lhs, depth)? != 0 || eval(rhs, depth)? != 0) as i64)
+        }
+        Expr::Binary(op, lhs, rhs) => apply_binary(op, eval(lhs, depth)?, eval(rhs, depth)?),
+        Expr::Ternary(condition, then_expr, else_expr) => {
+            if eval(condition, depth)? != 0 {
+                eval(then_expr, depth)
+            } else {
+                eval(else_expr, depth)
+            }
+        }
+        Expr::Assign(name, op, value) => {
+            let rhs = eval(value, depth)?;
+            let value = match op {
+                Some(op) => apply_binary(op, read_variable(name, depth)?, rhs)?,
+                None => rhs,
+            };
+            write_variable(name, value);
+            Ok(value)
+        }
+        Expr::Comma(lhs, rhs) => {
+            eval(lhs, depth)?;
+            eval(rhs, depth)
+        }
+    }
+}
+
+fn evaluate_with_depth(expr: &str, depth: usize) -> std::result::Result<i64, String> {
+    let tokens = lex(expr)?;
+    // 空表达式的值为 0
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let ast = parser.comma()?;
+    if let Some(token) = parser.tokens.get(parser.pos) {
+        let token = match token {
+            Token::Number(value) => value.to_string(),
+            Token::Ident(name) => name.clone(),
+            Token::Op(op) => op.to_string(),
+        };
+        return Err(format!(
+            "syntax error in expression (error token is \"{}\")",
+            token
+        ));
+    }
+    eval(&ast, depth)
+}
+
+// 变量从环境变量中读取，赋值也会写回环境变量
+pub fn evaluate(expr: &str) -> Result<i64> {
+    evaluate_with_depth(expr, 0).map_err(|err| format!("{}: {}", expr.trim(), err).into())
+}
+
+// 对单词中标记出的 $((expr)) 求值
+pub fn expand_arithmetic(word: &str) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = word;
+    while let Some(start) = rest.find(ARITHMETIC_START)
+        && let Some(len) = rest[start..].find(ARITHMETIC_END)
+    {
+        expanded.push_str(&rest[..start]);
+        let expr = &rest[start + ARITHMETIC_START.len_utf8()..start + len];
+        expanded.push_str(&evaluate(expr)?.to_string());
+        rest = &rest[start + len + ARITHMETIC_END.len_utf8()..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_str(expr: &str) -> i64 {
+        evaluate(expr).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_arithmetic() {
+        assert_eq!(eval_str("1 + 2 * 3"), 7);
+        assert_eq!(eval_str("(1 + 2) * 3"), 9);
+        assert_eq!(eval_str("2 ** 3 ** 2"), 512);
+        assert_eq!(eval_str("-2 ** 2"), 4);
+        assert_eq!(eval_str("7 / 2 + 7 % 2"), 4);
+        assert_eq!(eval_str("1 << 4 | 1 & 3 ^ 2"), 19);
+        assert_eq!(eval_str("!0 && 3 > 2 || 0"), 1);
+        assert_eq!(eval_str("~0"), -1);
+        assert_eq!(eval_str("0x1f + 010 + 2#101"), 44);
+        assert_eq!(eval_str("1 ? 2 : 3"), 2);
+        assert_eq!(eval_str("0 ? 2 : 0 ? 3 : 4"), 4);
+        assert_eq!(eval_str(""), 0);
+    }
+
+    #[test]
+    fn test_evaluate_variables() {
+        assert_eq!(eval_str("arith_x = 5, arith_x += 2, arith_x *= 3"), 21);
+        assert_eq!(eval_str("arith_x++"), 21);
+        assert_eq!(eval_str("++arith_x"), 23);
+        assert_eq!(eval_str("arith_x-- + $arith_x"), 45);
+        assert_eq!(eval_str("arith_x"), 22);
+        // 短路求值时右侧的赋值不会执行
+        assert_eq!(eval_str("0 && (arith_y = 1)"), 0);
+        assert_eq!(eval_str("arith_y"), 0);
+        assert_eq!(eval_str("arith_unset_var + 1"), 1);
+        unsafe { env::set_var("arith_expr", "arith_x * 2") };
+        assert_eq!(eval_str("arith_expr + 1"), 45);
+    }
+
+    #[test]
+    fn test_assignment_outside_main_thread() {
+        // 测试运行在非主线程中，赋值不会写入环境变量
+        assert_eq!(eval_str("arith_thread = 3"), 3);
+        assert_eq!(eval_str("arith_thread + 1"), 4);
+        assert!(env::var("arith_thread").is_err());
+        let other = std::thread::spawn(|| eval_str("arith_thread"))
+            .join()
+            .unwrap();
+        assert_eq!(other, 0);
+    }
+
+    #[test]
+    fn test_evaluate_errors() {
+        assert!(
+            evaluate("1 / 0")
+                .unwrap_err()
+                .to_string()
+                .contains("division by 0")
+        );
+        assert!(evaluate("5 % (2 - 2)").is_err());
+        assert!(evaluate("2 ** -1").is_err());
+        assert!(evaluate("1 +").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("1 2").is_err());
+        assert!(evaluate("3 = 4").is_err());
+        assert!(evaluate("09").is_err());
+    }
+
+    #[test]
+    fn test_expand_arithmetic() {
+        assert_eq!(expand_arithmetic("a\u{1}1 + 2\u{2}b").unwrap(), "a3b");
+        assert_eq!(
+            expand_arithmetic("\u{1}arith_z = 4\u{2}-\u{1}arith_z * 2\u{2}").unwrap(),
+            "4-8"
+        );
+        assert_eq!(expand_arithmetic("$((1))").unwrap(), "$((1))");
+        assert!(expand_arithmetic("\u{1}1 / 0\u{2}").is_err());
+    }
+}
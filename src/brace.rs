@@ -0,0 +1,256 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::Result;
+
+// 一个单词最多展开出的单词数，避免 {1..9999999999} 这样的序列耗尽内存
+const MAX_BRACE_WORDS: usize = 1 << 20;
+
+lazy_static! {
+    static ref NUMBER_SEQUENCE_RE: Regex =
+        Regex::new(r"^(-?\d+)\.\.(-?\d+)(?:\.\.(-?\d+))?$").unwrap();
+    static ref CHAR_SEQUENCE_RE: Regex =
+        Regex::new(r"^([A-Za-z])\.\.([A-Za-z])(?:\.\.(-?\d+))?$").unwrap();
+}
+
+// 单词中的一个字符，第二项表示它是否来自引号或转义，引号中的 { , } 不参与展开
+pub type WordChar = (char, bool);
+
+fn too_many_words(word: &[WordChar]) -> crate::Error {
+    let word: String = word.iter().map(|(c, _)| c).collect();
+    format!("{}: brace expansion produces too many words", word).into()
+}
+
+// 花括号展开，没有可展开的部分时返回原单词。展开结果保留每个字符是否被引用，用于后续的 ~ 展开
+pub fn expand_braces(word: &[WordChar]) -> Result<Vec<Vec<WordChar>>> {
+    let mut start = 0;
+    while let Some(open) = find_unquoted(word, start, '{') {
+        if let Some(close) = matching_brace(word, open)
+            && let Some(alternatives) = brace_alternatives(&word[open + 1..close])
+        {
+            let mut words = vec![];
+            for alternative in alternatives.ok_or_else(|| too_many_words(word))? {
+                let expanded: Vec<WordChar> = word[..open]
+                    .iter()
+                    .cloned()
+                    .chain(alternative)
+                    .chain(word[close + 1..].iter().cloned())
+                    .collect();
+                words.extend(expand_braces(&expanded)?);
+                if words.len() > MAX_BRACE_WORDS {
+                    return Err(too_many_words(word));
+                }
+            }
+            return Ok(words);
+        }
+        start = open + 1;
+    }
+    Ok(vec![word.to_vec()])
+}
+
+fn is_unquoted(word: &[WordChar], pos: usize, c: char) -> bool {
+    word[pos] == (c, false)
+}
+
+fn find_unquoted(word: &[WordChar], start: usize, c: char) -> Option<usize> {
+    (start..word.len()).find(|&pos| is_unquoted(word, pos, c))
+}
+
+fn matching_brace(word: &[WordChar], open: usize) -> Option<usize> {
+    // ${var} 是变量引用，不是花括号展开
+    if open > 0 && is_unquoted(word, open - 1, '$') {
+        return None;
+    }
+    let mut depth = 0;
+    for pos in open..word.len() {
+        if is_unquoted(word, pos, '{') {
+            depth += 1;
+        } else if is_unquoted(word, pos, '}') {
+            depth -= 1;
+            if depth == 0 {
+                return Some(pos);
+            }
+        }
+    }
+    None
+}
+
+// {a,b} 按顶层的逗号拆分，{x..y[..step]} 生成序列，{} 和 {x} 不展开，序列过长时返回 Some(None)
+fn brace_alternatives(content: &[WordChar]) -> Option<Option<Vec<Vec<WordChar>>>> {
+    let mut alternatives = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for pos in 0..content.len() {
+        if is_unquoted(content, pos, '{') {
+            depth += 1;
+        } else if is_unquoted(content, pos, '}') {
+            depth -= 1;
+        } else if depth == 0 && is_unquoted(content, pos, ',') {
+            alternatives.push(content[start..pos].to_vec());
+            start = pos + 1;
+        }
+    }
+    if !alternatives.is_empty() {
+        alternatives.push(content[start..].to_vec());
+        return Some(Some(alternatives));
+    }
+
+    if content.iter().any(|(_, quoted)| *quoted) {
+        return None;
+    }
+    let content: String = content.iter().map(|(c, _)| c).collect();
+    let sequence = expand_sequence(&content)?;
+    Some(sequence.map(|sequence| {
+        sequence
+            .into_iter()
+            .map(|item| item.chars().map(|c| (c, true)).collect())
+            .collect()
+    }))
+}
+
+fn sequence_step(step: Option<regex::Match>) -> Option<i64> {
+    match step {
+        Some(step) => step
+            .as_str()
+            .parse::<i64>()
+            .ok()
+            .and_then(i64::checked_abs)
+            .map(|step| step.max(1)),
+        None => Some(1),
+    }
+}
+
+// 超过 MAX_BRACE_WORDS 时返回 None
+fn sequence_values(start: i64, end: i64, step: i64) -> Option<Vec<i64>> {
+    let count = (end as i128 - start as i128).unsigned_abs() / step as u128 + 1;
+    if count > MAX_BRACE_WORDS as u128 {
+        return None;
+    }
+    let step = if start <= end { step } else { -step };
+    let mut values = vec![];
+    let mut value = Some(start);
+    while let Some(current) = value
+        && (if step > 0 {
+            current <= end
+        } else {
+            current >= end
+        })
+    {
+        values.push(current);
+        value = current.checked_add(step);
+    }
+    Some(values)
+}
+
+fn has_zero_padding(num: &str) -> bool {
+    let digits = num.strip_prefix('-').unwrap_or(num);
+    digits.len() > 1 && digits.starts_with('0')
+}
+
+// 不是序列时返回 None，序列过长时返回 Some(None)
+fn expand_sequence(content: &str) -> Option<Option<Vec<String>>> {
+    if let Some(caps) = NUMBER_SEQUENCE_RE.captures(content) {
+        let (start, end) = (&caps[1], &caps[2]);
+        let step = sequence_step(caps.get(3))?;
+        let Some(values) = sequence_values(start.parse().ok()?, end.parse().ok()?, step) else {
+            return Some(None);
+        };
+        // {01..10} 这样带前导 0 的序列按最长的一端补齐宽度
+        let width = if has_zero_padding(start) || has_zero_padding(end) {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+        return Some(Some(
+            values
+                .into_iter()
+                .map(|value| {
+                    if value < 0 {
+                        format!(
+                            "-{:0>width$}",
+                            value.unsigned_abs(),
+                            width = width.saturating_sub(1)
+                        )
+                    } else {
+                        format!("{:0>width$}", value, width = width)
+                    }
+                })
+                .collect(),
+        ));
+    }
+
+    let caps = CHAR_SEQUENCE_RE.captures(content)?;
+    let step = sequence_step(caps.get(3))?;
+    let start = caps[1].chars().next()? as i64;
+    let end = caps[2].chars().next()? as i64;
+    Some(sequence_values(start, end, step).map(|values| {
+        values
+            .into_iter()
+            .filter_map(|value| char::from_u32(value as u32))
+            .map(String::from)
+            .collect()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(word: &str) -> Vec<String> {
+        let word: Vec<WordChar> = word.chars().map(|c| (c, false)).collect();
+        to_strings(expand_braces(&word).unwrap())
+    }
+
+    fn to_strings(words: Vec<Vec<WordChar>>) -> Vec<String> {
+        words
+            .iter()
+            .map(|word| word.iter().map(|(c, _)| c).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_alternatives() {
+        assert_eq!(expand("src/{bin,lib}"), vec!["src/bin", "src/lib"]);
+        assert_eq!(expand("a{b,c}d{e,f}"), vec!["abde", "abdf", "acde", "acdf"]);
+        assert_eq!(expand("x{a,{b,c}}y"), vec!["xay", "xby", "xcy"]);
+        assert_eq!(expand("{,.bak}"), vec!["", ".bak"]);
+        assert_eq!(expand("{a{b,c}}"), vec!["{ab}", "{ac}"]);
+        assert_eq!(expand("{}"), vec!["{}"]);
+        assert_eq!(expand("{x}"), vec!["{x}"]);
+        assert_eq!(expand("${a,b}"), vec!["${a,b}"]);
+        assert_eq!(expand("{a,b"), vec!["{a,b"]);
+
+        let quoted: Vec<WordChar> = "{a,b}".chars().map(|c| (c, c == ',')).collect();
+        assert_eq!(to_strings(expand_braces(&quoted).unwrap()), vec!["{a,b}"]);
+    }
+
+    #[test]
+    fn test_expand_sequence() {
+        assert_eq!(expand("{1..5}"), vec!["1", "2", "3", "4", "5"]);
+        assert_eq!(expand("{1..10..3}"), vec!["1", "4", "7", "10"]);
+        assert_eq!(expand("{5..1..-2}"), vec!["5", "3", "1"]);
+        assert_eq!(expand("{-1..1}"), vec!["-1", "0", "1"]);
+        assert_eq!(expand("{01..10..4}"), vec!["01", "05", "09"]);
+        assert_eq!(expand("{-01..1}"), vec!["-01", "000", "001"]);
+        assert_eq!(expand("{a..e..2}"), vec!["a", "c", "e"]);
+        assert_eq!(expand("{c..a}"), vec!["c", "b", "a"]);
+        assert_eq!(expand("f{1..2}{a,b}"), vec!["f1a", "f1b", "f2a", "f2b"]);
+        assert_eq!(expand("{1..a}"), vec!["{1..a}"]);
+        assert_eq!(expand("{1..}"), vec!["{1..}"]);
+        assert_eq!(
+            expand("{9223372036854775806..9223372036854775807..5}"),
+            vec!["9223372036854775806"]
+        );
+        assert_eq!(
+            expand("{-9223372036854775807..-9223372036854775808}"),
+            vec!["-9223372036854775807", "-9223372036854775808"]
+        );
+    }
+
+    #[test]
+    fn test_expand_too_many_words() {
+        let word = |word: &str| -> Vec<WordChar> { word.chars().map(|c| (c, false)).collect() };
+        assert!(expand_braces(&word("{1..9999999999}")).is_err());
+        assert!(expand_braces(&word("{1..2000}{1..2000}")).is_err());
+    }
+}
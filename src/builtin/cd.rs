@@ -0,0 +1,213 @@
+use std::{env, io::Write, path::PathBuf};
+
+use crate::{
+    Result,
+    builtin::ExitCode,
+    command::{Execute, Parse, ParseCommandError},
+    directory::{change_dir, describe_dir_error, logical_cwd, physical_cwd},
+    map_err_to_exit_code,
+    redirect::{Reader, Writer},
+};
+
+// -L 和 -P 可以出现多次，以最后一次为准
+fn parse_symlink_flags<'a>(command: &str, args: &'a [String]) -> Result<(bool, &'a [String])> {
+    let mut physical = false;
+    let mut idx = 0;
+    while idx < args.len() && args[idx].starts_with('-') && args[idx].len() > 1 {
+        if args[idx] == "--" {
+            idx += 1;
+            break;
+        }
+        if args[idx] == "-" {
+            break;
+        }
+        for flag in args[idx][1..].chars() {
+            match flag {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => {
+                    return Err(format!("{}: -{}: invalid option", command, flag).into());
+                }
+            }
+        }
+        idx += 1;
+    }
+    Ok((physical, &args[idx..]))
+}
+
+// cd [-L|-P] [dir]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cd {
+    pub dir: Option<String>,
+    pub physical: bool,
+}
+
+impl Parse for Cd {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let (physical, args) = parse_symlink_flags(command, args)?;
+        if args.len() > 1 {
+            return Err(ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 1).into());
+        }
+        Ok(Cd {
+            dir: args.first().cloned(),
+            physical,
+        })
+    }
+}
+
+impl Cd {
+    // 返回目标路径，以及是否需要打印新的目录 (cd - 和通过 CDPATH 找到的目录)
+    fn resolve_target(&self) -> std::result::Result<(PathBuf, bool), String> {
+        let dir = match self.dir.as_deref() {
+            None => {
+                return env::var("HOME")
+                    .map(|home| (PathBuf::from(home), false))
+                    .map_err(|_| "cd: HOME not set".to_string());
+            }
+            Some("-") => {
+                return env::var("OLDPWD")
+                    .map(|old_pwd| (PathBuf::from(old_pwd), true))
+                    .map_err(|_| "cd: OLDPWD not set".to_string());
+            }
+            Some(dir) => dir.to_string(),
+        };
+
+        let is_relative_to_cwd = dir.starts_with('/')
+            || dir == "."
+            || dir == ".."
+            || dir.starts_with("./")
+            || dir.starts_with("../");
+        if !is_relative_to_cwd && let Ok(cdpath) = env::var("CDPATH") {
+            for base in env::split_paths(&cdpath) {
+                let candidate = base.join(&dir);
+                if candidate.is_dir() {
+                    // 空的 CDPATH 项表示当前目录，此时不打印
+                    let print = !base.as_os_str().is_empty();
+                    return Ok((candidate, print));
+                }
+            }
+        }
+        Ok((PathBuf::from(dir), false))
+    }
+}
+
+impl Execute for Cd {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let (target, print) = match self.resolve_target() {
+            Ok(target) => target,
+            Err(err) => {
+                writeln!(error_writer, "{}", err).ok();
+                return -1;
+            }
+        };
+        match change_dir(&target, self.physical) {
+            Ok(new_pwd) => {
+                if print {
+                    map_err_to_exit_code!(writeln!(output_writer, "{}", new_pwd.display()));
+                }
+                0
+            }
+            Err(err) => {
+                writeln!(
+                    error_writer,
+                    "cd: {}: {}",
+                    target.display(),
+                    describe_dir_error(&err)
+                )
+                .ok();
+                -1
+            }
+        }
+    }
+}
+
+// pwd [-L|-P]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pwd {
+    pub physical: bool,
+}
+
+impl Parse for Pwd {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let (physical, args) = parse_symlink_flags(command, args)?;
+        if !args.is_empty() {
+            return Err(ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 0).into());
+        }
+        Ok(Pwd { physical })
+    }
+}
+
+impl Execute for Pwd {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let pwd = if self.physical {
+            physical_cwd()
+        } else {
+            Ok(logical_cwd())
+        };
+        if let Ok(pwd) = pwd {
+            -(writeln!(output_writer, "{}", pwd.display()).is_err() as ExitCode)
+        } else {
+            let _ = writeln!(error_writer, "invalid directory");
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_cd() {
+        assert_eq!(
+            Cd::parse("cd", &[]).unwrap(),
+            Cd {
+                dir: None,
+                physical: false
+            }
+        );
+        assert_eq!(
+            Cd::parse("cd", &vec_str_to_vec_string::<Vec<_>>(&["-LP", "-"])).unwrap(),
+            Cd {
+                dir: Some("-".to_string()),
+                physical: true
+            }
+        );
+        assert_eq!(
+            Cd::parse(
+                "cd",
+                &vec_str_to_vec_string::<Vec<_>>(&["-P", "-L", "--", "-x"])
+            )
+            .unwrap(),
+            Cd {
+                dir: Some("-x".to_string()),
+                physical: false
+            }
+        );
+        assert!(Cd::parse("cd", &vec_str_to_vec_string::<Vec<_>>(&["-x"])).is_err());
+        assert!(Cd::parse("cd", &vec_str_to_vec_string::<Vec<_>>(&["a", "b"])).is_err());
+        assert_eq!(
+            Pwd::parse("pwd", &vec_str_to_vec_string::<Vec<_>>(&["-P"])).unwrap(),
+            Pwd { physical: true }
+        );
+        assert!(Pwd::parse("pwd", &vec_str_to_vec_string::<Vec<_>>(&["a"])).is_err());
+    }
+}
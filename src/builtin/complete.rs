@@ -0,0 +1,217 @@
+use std::io::Write;
+
+use crate::{
+    Result,
+    builtin::ExitCode,
+    command::{Execute, Parse},
+    completer::{COMPLETION_SPECS, CompletionAction, CompletionSpec},
+    map_err_to_exit_code,
+    redirect::{Reader, Writer},
+};
+
+// complete [-fdcb] [-o option] [-A action] [-W wordlist] [-F function] [-C command] name...
+// complete -p [name...]
+// complete -r [name...]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Complete {
+    Print(Vec<String>),
+    Remove(Vec<String>),
+    Define(CompletionSpec, Vec<String>),
+}
+
+impl Parse for Complete {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let mut spec = CompletionSpec::default();
+        let mut print = args.is_empty();
+        let mut remove = false;
+
+        let mut idx = 0;
+        while idx < args.len() && args[idx].starts_with('-') && args[idx].len() > 1 {
+            let flags: Vec<char> = args[idx][1..].chars().collect();
+            idx += 1;
+            for flag in flags {
+                match flag {
+                    'f' => spec.actions.push(CompletionAction::File),
+                    'd' => spec.actions.push(CompletionAction::Directory),
+                    'c' => spec.actions.push(CompletionAction::Command),
+                    'b' => spec.actions.push(CompletionAction::Builtin),
+                    'v' => spec.actions.push(CompletionAction::Variable),
+                    'p' => print = true,
+                    'r' => remove = true,
+                    'A' | 'W' | 'F' | 'C' | 'o' => {
+                        let Some(value) = args.get(idx) else {
+                            return Err(format!(
+                                "{}: -{}: option requires an argument",
+                                command, flag
+                            )
+                            .into());
+                        };
+                        idx += 1;
+                        match flag {
+                            'A' => {
+                                spec.actions
+                                    .push(CompletionAction::from_name(value).ok_or_else(|| {
+                                        format!("{}: {}: invalid action name", command, value)
+                                    })?)
+                            }
+                            'W' => spec.word_list = Some(value.to_string()),
+                            'F' => spec.function = Some(value.to_string()),
+                            'C' => spec.command = Some(value.to_string()),
+                            'o' => match value.as_str() {
+                                "nospace" => spec.nospace = true,
+                                "default" => spec.default = true,
+                                "dirnames" => spec.actions.push(CompletionAction::Directory),
+                                "filenames" => {}
+                                _ => {
+                                    return Err(format!(
+                                        "{}: {}: invalid option name",
+                                        command, value
+                                    )
+                                    .into());
+                                }
+                            },
+                            _ => unreachable!(),
+                        }
+                    }
+                    _ => {
+                        return Err(format!("{}: -{}: invalid option", command, flag).into());
+                    }
+                }
+            }
+        }
+
+        let names = args[idx..].to_vec();
+        if print {
+            Ok(Complete::Print(names))
+        } else if remove {
+            Ok(Complete::Remove(names))
+        } else if names.is_empty() {
+            Err(format!("{}: missing command name", command).into())
+        } else {
+            Ok(Complete::Define(spec, names))
+        }
+    }
+}
+
+impl Execute for Complete {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        match self {
+            Complete::Print(names) => {
+                let specs = map_err_to_exit_code!(COMPLETION_SPECS.read());
+                let mut names = names.clone();
+                if names.is_empty() {
+                    names = specs.keys().cloned().collect();
+                    names.sort();
+                }
+                let mut exit_code = 0;
+                for name in names {
+                    if let Some(spec) = specs.get(&name) {
+                        map_err_to_exit_code!(writeln!(
+                            output_writer,
+                            "complete {} {}",
+                            spec, name
+                        ));
+                    } else {
+                        writeln!(
+                            error_writer,
+                            "complete: {}: no completion specification",
+                            name
+                        )
+                        .ok();
+                        exit_code = -1;
+                    }
+                }
+                exit_code
+            }
+            Complete::Remove(names) => {
+                let mut specs = map_err_to_exit_code!(COMPLETION_SPECS.write());
+                if names.is_empty() {
+                    specs.clear();
+                    return 0;
+                }
+                let mut exit_code = 0;
+                for name in names {
+                    if specs.remove(name).is_none() {
+                        writeln!(
+                            error_writer,
+                            "complete: {}: no completion specification",
+                            name
+                        )
+                        .ok();
+                        exit_code = -1;
+                    }
+                }
+                exit_code
+            }
+            Complete::Define(spec, names) => {
+                let mut specs = map_err_to_exit_code!(COMPLETION_SPECS.write());
+                for name in names {
+                    specs.insert(name.to_string(), spec.clone());
+                }
+                0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_complete() {
+        assert_eq!(
+            Complete::parse("complete", &[]).unwrap(),
+            Complete::Print(vec![])
+        );
+        assert_eq!(
+            Complete::parse(
+                "complete",
+                &vec_str_to_vec_string::<Vec<_>>(&[
+                    "-fd",
+                    "-o",
+                    "nospace",
+                    "-W",
+                    "build test",
+                    "mytool"
+                ])
+            )
+            .unwrap(),
+            Complete::Define(
+                CompletionSpec {
+                    actions: vec![CompletionAction::File, CompletionAction::Directory],
+                    word_list: Some("build test".to_string()),
+                    nospace: true,
+                    ..Default::default()
+                },
+                vec!["mytool".to_string()]
+            )
+        );
+        assert_eq!(
+            Complete::parse(
+                "complete",
+                &vec_str_to_vec_string::<Vec<_>>(&["-r", "mytool"])
+            )
+            .unwrap(),
+            Complete::Remove(vec!["mytool".to_string()])
+        );
+        assert!(Complete::parse("complete", &vec_str_to_vec_string::<Vec<_>>(&["-W"])).is_err());
+        assert!(
+            Complete::parse(
+                "complete",
+                &vec_str_to_vec_string::<Vec<_>>(&["-A", "foo", "x"])
+            )
+            .is_err()
+        );
+    }
+}
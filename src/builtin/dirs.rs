@@ -0,0 +1,309 @@
+use std::{io::Write, path::PathBuf};
+
+use crate::{
+    Result,
+    builtin::ExitCode,
+    command::{Execute, Parse, ParseCommandError},
+    directory::{
+        DIR_STACK, abbreviate_home, change_dir, describe_dir_error, dir_stack_entries,
+        dir_stack_index,
+    },
+    map_err_to_exit_code,
+    redirect::{Reader, Writer},
+    utils::is_main_thread,
+};
+
+fn is_stack_index(arg: &str) -> bool {
+    (arg.starts_with('+') || arg.starts_with('-'))
+        && arg.len() > 1
+        && arg[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+// 切换到新的栈顶，并用 entries[1..] 替换目录栈，栈顶替换为切换后的目录
+fn switch_stack(
+    command: &str,
+    entries: &mut [PathBuf],
+    error_writer: &mut Writer,
+) -> std::result::Result<(), ExitCode> {
+    match change_dir(&entries[0], false) {
+        Ok(new_pwd) => entries[0] = new_pwd,
+        Err(err) => {
+            writeln!(
+                error_writer,
+                "{}: {}: {}",
+                command,
+                entries[0].display(),
+                describe_dir_error(&err)
+            )
+            .ok();
+            return Err(-1);
+        }
+    }
+    replace_stack(&entries[1..]);
+    Ok(())
+}
+
+// 和 cd 一样，管道线程中不修改目录栈
+fn replace_stack(stack: &[PathBuf]) {
+    if is_main_thread() {
+        *DIR_STACK.lock().unwrap_or_else(|err| err.into_inner()) = stack.to_vec();
+    }
+}
+
+// 管道线程中不会修改目录栈，所以输出的是命令计算出的新栈，而不是重新读取全局的目录栈
+fn print_stack(entries: &[PathBuf], output_writer: &mut Writer) -> ExitCode {
+    let line = entries
+        .iter()
+        .map(|dir| abbreviate_home(dir))
+        .collect::<Vec<_>>()
+        .join(" ");
+    -(writeln!(output_writer, "{}", line).is_err() as ExitCode)
+}
+
+// pushd [dir | +N | -N]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Pushd {
+    Swap,
+    Rotate(String),
+    Dir(String),
+}
+
+impl Parse for Pushd {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        if args.len() > 1 {
+            return Err(ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 1).into());
+        }
+        let pushd = match args.first() {
+            None => Pushd::Swap,
+            Some(arg) if is_stack_index(arg) => Pushd::Rotate(arg.to_string()),
+            Some(arg) => Pushd::Dir(arg.to_string()),
+        };
+        Ok(pushd)
+    }
+}
+
+impl Execute for Pushd {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let mut entries = dir_stack_entries();
+        match self {
+            Pushd::Swap => {
+                if entries.len() < 2 {
+                    writeln!(error_writer, "pushd: no other directory").ok();
+                    return -1;
+                }
+                entries.swap(0, 1);
+            }
+            Pushd::Rotate(spec) => {
+                let Some(idx) = dir_stack_index(spec, entries.len()) else {
+                    writeln!(
+                        error_writer,
+                        "pushd: {}: directory stack index out of range",
+                        spec
+                    )
+                    .ok();
+                    return -1;
+                };
+                entries.rotate_left(idx);
+            }
+            Pushd::Dir(dir) => entries.insert(0, PathBuf::from(dir)),
+        }
+        if let Err(exit_code) = switch_stack("pushd", &mut entries, &mut error_writer) {
+            return exit_code;
+        }
+        print_stack(&entries, &mut output_writer)
+    }
+}
+
+// popd [+N | -N]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Popd {
+    pub index: Option<String>,
+}
+
+impl Parse for Popd {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        match args {
+            [] => Ok(Popd { index: None }),
+            [arg] if is_stack_index(arg) => Ok(Popd {
+                index: Some(arg.to_string()),
+            }),
+            [arg] => Err(format!("{}: {}: invalid argument", command, arg).into()),
+            _ => Err(ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 1).into()),
+        }
+    }
+}
+
+impl Execute for Popd {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let mut entries = dir_stack_entries();
+        if entries.len() < 2 {
+            writeln!(error_writer, "popd: directory stack empty").ok();
+            return -1;
+        }
+        let idx = match &self.index {
+            None => 0,
+            Some(spec) => match dir_stack_index(spec, entries.len()) {
+                Some(idx) => idx,
+                None => {
+                    writeln!(
+                        error_writer,
+                        "popd: {}: directory stack index out of range",
+                        spec
+                    )
+                    .ok();
+                    return -1;
+                }
+            },
+        };
+        entries.remove(idx);
+        if idx == 0 {
+            if let Err(exit_code) = switch_stack("popd", &mut entries, &mut error_writer) {
+                return exit_code;
+            }
+        } else {
+            replace_stack(&entries[1..]);
+        }
+        print_stack(&entries, &mut output_writer)
+    }
+}
+
+// dirs [-clpv] [+N | -N]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Dirs {
+    pub clear: bool,
+    pub long: bool,
+    pub per_line: bool,
+    pub verbose: bool,
+    pub index: Option<String>,
+}
+
+impl Parse for Dirs {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let mut dirs = Dirs::default();
+        for arg in args {
+            if is_stack_index(arg) {
+                dirs.index = Some(arg.to_string());
+                continue;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+                return Err(format!("{}: {}: invalid argument", command, arg).into());
+            };
+            for flag in flags.chars() {
+                match flag {
+                    'c' => dirs.clear = true,
+                    'l' => dirs.long = true,
+                    'p' => dirs.per_line = true,
+                    'v' => dirs.verbose = true,
+                    _ => return Err(format!("{}: -{}: invalid option", command, flag).into()),
+                }
+            }
+        }
+        Ok(dirs)
+    }
+}
+
+impl Execute for Dirs {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        if self.clear {
+            replace_stack(&[]);
+            return 0;
+        }
+
+        let format_dir = |dir: &PathBuf| {
+            if self.long {
+                dir.display().to_string()
+            } else {
+                abbreviate_home(dir)
+            }
+        };
+        let entries = dir_stack_entries();
+        if let Some(spec) = &self.index {
+            let Some(idx) = dir_stack_index(spec, entries.len()) else {
+                writeln!(
+                    error_writer,
+                    "dirs: {}: directory stack index out of range",
+                    spec
+                )
+                .ok();
+                return -1;
+            };
+            map_err_to_exit_code!(writeln!(output_writer, "{}", format_dir(&entries[idx])));
+            return 0;
+        }
+
+        if self.verbose {
+            for (idx, dir) in entries.iter().enumerate() {
+                map_err_to_exit_code!(writeln!(output_writer, "{:2}  {}", idx, format_dir(dir)));
+            }
+        } else if self.per_line {
+            for dir in &entries {
+                map_err_to_exit_code!(writeln!(output_writer, "{}", format_dir(dir)));
+            }
+        } else {
+            let line = entries.iter().map(format_dir).collect::<Vec<_>>().join(" ");
+            map_err_to_exit_code!(writeln!(output_writer, "{}", line));
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_dir_stack() {
+        assert_eq!(Pushd::parse("pushd", &[]).unwrap(), Pushd::Swap);
+        assert_eq!(
+            Pushd::parse("pushd", &vec_str_to_vec_string::<Vec<_>>(&["+2"])).unwrap(),
+            Pushd::Rotate("+2".to_string())
+        );
+        assert_eq!(
+            Pushd::parse("pushd", &vec_str_to_vec_string::<Vec<_>>(&["/tmp"])).unwrap(),
+            Pushd::Dir("/tmp".to_string())
+        );
+        assert_eq!(
+            Popd::parse("popd", &vec_str_to_vec_string::<Vec<_>>(&["-1"])).unwrap(),
+            Popd {
+                index: Some("-1".to_string())
+            }
+        );
+        assert!(Popd::parse("popd", &vec_str_to_vec_string::<Vec<_>>(&["/tmp"])).is_err());
+        assert_eq!(
+            Dirs::parse("dirs", &vec_str_to_vec_string::<Vec<_>>(&["-lv", "+1"])).unwrap(),
+            Dirs {
+                long: true,
+                verbose: true,
+                index: Some("+1".to_string()),
+                ..Default::default()
+            }
+        );
+        assert!(Dirs::parse("dirs", &vec_str_to_vec_string::<Vec<_>>(&["-x"])).is_err());
+    }
+}
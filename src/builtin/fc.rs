@@ -0,0 +1,389 @@
+use std::{
+    collections::hash_map::RandomState,
+    env, fs,
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    process,
+    sync::atomic::Ordering,
+    time::Instant,
+};
+
+use crate::{
+    Result,
+    builtin::ExitCode,
+    command::{Execute, LAST_EXIT_CODE, Parse, ParseCommandError},
+    history::{
+        CURRENT_LINE_ADDED, EntryMetadata, HistoryEntry, finish_last_entry, forget_current_line,
+        history_entries, remember_command,
+    },
+    map_err_to_exit_code,
+    redirect::{Reader, Writer},
+    run_line,
+};
+
+// 数字按 history 的编号解析，超出范围时取最近的一端；其他的匹配最近一条以它开头的命令
+fn resolve_spec(spec: &str, lines: &[String]) -> Option<usize> {
+    if lines.is_empty() {
+        return None;
+    }
+    match spec.parse::<i64>() {
+        Ok(offset) => {
+            let len = lines.len() as i64;
+            let idx = if offset <= 0 {
+                len + offset
+            } else {
+                offset - 1
+            };
+            Some(idx.clamp(0, len - 1) as usize)
+        }
+        Err(_) => lines.iter().rposition(|line| line.starts_with(spec)),
+    }
+}
+
+// fc 的 [first [last]] 参数
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FcRange {
+    first: Option<String>,
+    last: Option<String>,
+    reverse: bool,
+}
+
+impl FcRange {
+    // 返回选中的 (下标, 命令)，first 在 last 之后时倒序排列，last 缺省时为 default_last 或者 first
+    fn select<'a>(
+        &self,
+        lines: &'a [String],
+        default_first: &str,
+        default_last: Option<&str>,
+    ) -> std::result::Result<Vec<(usize, &'a String)>, String> {
+        let first = self.first.as_deref().unwrap_or(default_first);
+        let last = self.last.as_deref().or(default_last).unwrap_or(first);
+        let (Some(first), Some(last)) = (resolve_spec(first, lines), resolve_spec(last, lines))
+        else {
+            return Err("fc: history specification out of range".to_string());
+        };
+        let mut selected: Vec<_> = lines
+            .iter()
+            .enumerate()
+            .skip(first.min(last))
+            .take(first.abs_diff(last) + 1)
+            .collect();
+        if (first > last) != self.reverse {
+            selected.reverse();
+        }
+        Ok(selected)
+    }
+}
+
+// fc -l [-nr] [first [last]], fc [-e editor] [-r] [first [last]], fc -s [old=new] [command]
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fc {
+    List(FcRange, bool),
+    Edit(Option<String>, FcRange),
+    Substitute(Option<(String, String)>, Option<String>),
+}
+
+impl Parse for Fc {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let (mut list, mut numbered, mut substitute) = (false, true, false);
+        let mut editor = None;
+        let mut range = FcRange::default();
+        let mut idx = 0;
+        while let Some(arg) = args.get(idx) {
+            if arg == "--" {
+                idx += 1;
+                break;
+            }
+            // -5 这样的负数是历史记录的位置而不是选项
+            if !arg.starts_with('-') || arg.len() == 1 || arg[1..].parse::<u64>().is_ok() {
+                break;
+            }
+            for option in arg[1..].chars() {
+                match option {
+                    'l' => list = true,
+                    'n' => numbered = false,
+                    'r' => range.reverse = true,
+                    's' => substitute = true,
+                    'e' => {
+                        idx += 1;
+                        let name = args.get(idx).ok_or_else(|| {
+                            format!("{}: -e: option requires an argument", command)
+                        })?;
+                        editor = Some(name.clone());
+                    }
+                    _ => return Err(format!("{}: -{}: invalid option", command, option).into()),
+                }
+            }
+            idx += 1;
+        }
+
+        let mut operands = &args[idx..];
+        // fc -e - 和 fc -s 一样直接重新执行
+        if substitute || editor.as_deref() == Some("-") {
+            let pattern = match operands.first().and_then(|arg| arg.split_once('=')) {
+                Some((old, new)) => {
+                    operands = &operands[1..];
+                    Some((old.to_string(), new.to_string()))
+                }
+                None => None,
+            };
+            if operands.len() > 1 {
+                return Err(ParseCommandError::MoreArgs(
+                    command.to_string(),
+                    args.to_vec(),
+                    idx + 1,
+                )
+                .into());
+            }
+            return Ok(Fc::Substitute(pattern, operands.first().cloned()));
+        }
+
+        if operands.len() > 2 {
+            return Err(
+                ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), idx + 2).into(),
+            );
+        }
+        range.first = operands.first().cloned();
+        range.last = operands.get(1).cloned();
+        Ok(if list {
+            Fc::List(range, numbered)
+        } else {
+            Fc::Edit(editor, range)
+        })
+    }
+}
+
+// 依次显示并执行命令，它们会代替 fc 本身记录到历史中
+fn run_commands(commands: Vec<String>, error_writer: &mut Writer) -> ExitCode {
+    forget_current_line();
+    for line in commands {
+        writeln!(error_writer, "{}", line).ok();
+        let added = remember_command(HistoryEntry {
+            line: line.clone(),
+            metadata: EntryMetadata::now(),
+        });
+        let start = Instant::now();
+        run_line(&line);
+        if added {
+            finish_last_entry(start.elapsed(), LAST_EXIT_CODE.load(Ordering::Relaxed));
+        }
+    }
+    LAST_EXIT_CODE.load(Ordering::Relaxed)
+}
+
+// 把命令写入临时文件并用编辑器打开，编辑器成功退出后返回编辑后的命令
+// 创建只有当前用户可读写的临时文件，文件名随机，已经存在时换一个名字重试
+fn create_temp_file() -> Result<(PathBuf, fs::File)> {
+    for _ in 0..100 {
+        let suffix = RandomState::new().build_hasher().finish();
+        let file = env::temp_dir().join(format!("fc-{}-{:016x}.sh", process::id(), suffix));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&file)
+        {
+            Ok(fp) => return Ok((file, fp)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("fc: cannot create temp file: {}", err).into()),
+        }
+    }
+    Err("fc: cannot create temp file".into())
+}
+
+fn edit_commands(editor: Option<&str>, commands: &[&String]) -> Result<Vec<String>> {
+    let editor = editor
+        .map(str::to_string)
+        .or_else(|| env::var("FCEDIT").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let (file, mut fp) = create_temp_file()?;
+    let content: String = commands.iter().map(|line| format!("{}\n", line)).collect();
+    let written = fp.write_all(content.as_bytes());
+    drop(fp);
+    if let Err(err) = written {
+        fs::remove_file(&file).ok();
+        return Err(err.into());
+    }
+
+    let mut words = editor.split_whitespace();
+    let status = process::Command::new(words.next().unwrap_or("vi"))
+        .args(words)
+        .arg(&file)
+        .status();
+    let edited = fs::read_to_string(&file);
+    fs::remove_file(&file).ok();
+    let status = status.map_err(|err| format!("fc: {}: {}", editor, err))?;
+    if !status.success() {
+        return Err(format!("fc: {}: editor failed with {}", editor, status).into());
+    }
+    Ok(edited?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+impl Execute for Fc {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let mut lines: Vec<String> = history_entries()
+            .into_iter()
+            .map(|entry| entry.line)
+            .collect();
+        // fc 这条命令本身不参与选择
+        if CURRENT_LINE_ADDED.load(Ordering::Relaxed) {
+            lines.pop();
+        }
+
+        match self {
+            Fc::List(range, numbered) => {
+                let selected = match range.select(&lines, "-16", Some("-1")) {
+                    Ok(selected) => selected,
+                    Err(err) => {
+                        writeln!(error_writer, "{}", err).ok();
+                        return -1;
+                    }
+                };
+                for (idx, line) in selected {
+                    if *numbered {
+                        map_err_to_exit_code!(writeln!(output_writer, "{}\t {}", idx + 1, line));
+                    } else {
+                        map_err_to_exit_code!(writeln!(output_writer, "\t {}", line));
+                    }
+                }
+                0
+            }
+            Fc::Edit(editor, range) => {
+                let selected = range.select(&lines, "-1", None).map(|selected| {
+                    selected
+                        .into_iter()
+                        .map(|(_, line)| line)
+                        .collect::<Vec<_>>()
+                });
+                let edited = selected
+                    .map_err(|err| err.into())
+                    .and_then(|selected| edit_commands(editor.as_deref(), &selected));
+                match edited {
+                    Ok(commands) => run_commands(commands, &mut error_writer),
+                    Err(err) => {
+                        writeln!(error_writer, "{}", err).ok();
+                        -1
+                    }
+                }
+            }
+            Fc::Substitute(pattern, spec) => {
+                let Some(idx) = resolve_spec(spec.as_deref().unwrap_or("-1"), &lines) else {
+                    writeln!(error_writer, "fc: no command found").ok();
+                    return -1;
+                };
+                let mut line = lines[idx].clone();
+                if let Some((old, new)) = pattern
+                    && !old.is_empty()
+                {
+                    line = line.replace(old, new);
+                }
+                run_commands(vec![line], &mut error_writer)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_fc() {
+        let parse = |args: &[&str]| Fc::parse("fc", &vec_str_to_vec_string::<Vec<_>>(args));
+        let range = |first: Option<&str>, last: Option<&str>, reverse| FcRange {
+            first: first.map(str::to_string),
+            last: last.map(str::to_string),
+            reverse,
+        };
+        assert_eq!(
+            parse(&["-l"]).unwrap(),
+            Fc::List(range(None, None, false), true)
+        );
+        assert_eq!(
+            parse(&["-lnr", "-5", "ec"]).unwrap(),
+            Fc::List(range(Some("-5"), Some("ec"), true), false)
+        );
+        assert_eq!(
+            parse(&["-e", "nano", "3"]).unwrap(),
+            Fc::Edit(Some("nano".to_string()), range(Some("3"), None, false))
+        );
+        assert_eq!(parse(&["-s"]).unwrap(), Fc::Substitute(None, None));
+        assert_eq!(
+            parse(&["-s", "a=b", "echo"]).unwrap(),
+            Fc::Substitute(
+                Some(("a".to_string(), "b".to_string())),
+                Some("echo".to_string())
+            )
+        );
+        assert_eq!(
+            parse(&["-e", "-", "-2"]).unwrap(),
+            Fc::Substitute(None, Some("-2".to_string()))
+        );
+        assert!(parse(&["-e"]).is_err());
+        assert!(parse(&["-x"]).is_err());
+        assert!(parse(&["-l", "1", "2", "3"]).is_err());
+        assert!(parse(&["-s", "a", "b"]).is_err());
+    }
+
+    #[test]
+    fn test_select_range() {
+        let lines: Vec<String> = vec_str_to_vec_string(&["ls", "echo a", "cd /", "echo b"]);
+        let select = |first: Option<&str>, last: Option<&str>, reverse| {
+            FcRange {
+                first: first.map(str::to_string),
+                last: last.map(str::to_string),
+                reverse,
+            }
+            .select(&lines, "-16", Some("-1"))
+            .unwrap()
+            .into_iter()
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(select(None, None, false), vec![0, 1, 2, 3]);
+        assert_eq!(select(Some("2"), Some("3"), false), vec![1, 2]);
+        assert_eq!(select(Some("-1"), Some("-2"), false), vec![3, 2]);
+        assert_eq!(select(Some("2"), Some("3"), true), vec![2, 1]);
+        assert_eq!(select(Some("echo"), None, false), vec![3]);
+        assert_eq!(select(Some("ec"), Some("l"), false), vec![3, 2, 1, 0]);
+        assert_eq!(select(Some("100"), None, false), vec![3]);
+
+        let range = FcRange {
+            first: Some("git".to_string()),
+            ..Default::default()
+        };
+        assert!(range.select(&lines, "-1", None).is_err());
+        assert!(FcRange::default().select(&[], "-1", None).is_err());
+    }
+
+    #[test]
+    fn test_create_temp_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (first, _) = create_temp_file().unwrap();
+        let (second, _) = create_temp_file().unwrap();
+        let mode = fs::metadata(&first).unwrap().permissions().mode();
+        fs::remove_file(&first).ok();
+        fs::remove_file(&second).ok();
+        assert_ne!(first, second);
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
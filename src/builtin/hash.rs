@@ -0,0 +1,161 @@
+use std::{io::Write, path::PathBuf};
+
+use crate::{
+    Result,
+    builtin::ExitCode,
+    command::{Execute, Parse},
+    command_index::SUPPORT_COMMANDS,
+    executable::{HASHED_COMMANDS, find_in_path},
+    map_err_to_exit_code,
+    redirect::{Reader, Writer},
+};
+
+// hash [name...], hash -r, hash -d name..., hash -p path name, hash -t name...
+#[derive(Debug, PartialEq, Eq)]
+pub enum Hash {
+    Show,
+    Reset,
+    Add(Vec<String>),
+    Delete(Vec<String>),
+    Set(PathBuf, Vec<String>),
+    Print(Vec<String>),
+}
+
+impl Parse for Hash {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let hash = match args.first().map(|arg| arg.as_str()) {
+            None => Hash::Show,
+            Some("-r") => Hash::Reset,
+            Some("-d") if args.len() > 1 => Hash::Delete(args[1..].to_vec()),
+            Some("-t") if args.len() > 1 => Hash::Print(args[1..].to_vec()),
+            Some("-p") if args.len() > 2 => Hash::Set(PathBuf::from(&args[1]), args[2..].to_vec()),
+            Some("-d" | "-t" | "-p") => {
+                return Err(
+                    format!("{}: {}: option requires an argument", command, args[0]).into(),
+                );
+            }
+            Some(arg) if arg.starts_with('-') => {
+                return Err(format!("{}: {}: invalid option", command, arg).into());
+            }
+            Some(_) => Hash::Add(args.to_vec()),
+        };
+        Ok(hash)
+    }
+}
+
+impl Execute for Hash {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let mut table = map_err_to_exit_code!(HASHED_COMMANDS.lock());
+        let mut exit_code = 0;
+        match self {
+            Hash::Show => {
+                let entries = table.entries();
+                if entries.is_empty() {
+                    map_err_to_exit_code!(writeln!(output_writer, "hash: hash table empty"));
+                } else {
+                    map_err_to_exit_code!(writeln!(output_writer, "hits\tcommand"));
+                    for hashed in entries.values() {
+                        map_err_to_exit_code!(writeln!(
+                            output_writer,
+                            "{:4}\t{}",
+                            hashed.hits,
+                            hashed.path.display()
+                        ));
+                    }
+                }
+            }
+            Hash::Reset => {
+                table.clear();
+                // 同时重建补全使用的命令索引
+                map_err_to_exit_code!(SUPPORT_COMMANDS.write()).rebuild();
+            }
+            Hash::Add(names) => {
+                for name in names {
+                    if table.get(name).is_some() {
+                        continue;
+                    }
+                    match find_in_path(name) {
+                        Some(path) if !name.contains('/') => table.remember(name, path),
+                        Some(_) => {}
+                        None => {
+                            writeln!(error_writer, "hash: {}: not found", name).ok();
+                            exit_code = -1;
+                        }
+                    }
+                }
+            }
+            Hash::Delete(names) => {
+                for name in names {
+                    if !table.forget(name) {
+                        writeln!(error_writer, "hash: {}: not found", name).ok();
+                        exit_code = -1;
+                    }
+                }
+            }
+            Hash::Set(path, names) => {
+                for name in names {
+                    table.remember(name, path.clone());
+                }
+            }
+            Hash::Print(names) => {
+                for name in names {
+                    if let Some(hashed) = table.get(name) {
+                        map_err_to_exit_code!(writeln!(output_writer, "{}", hashed.path.display()));
+                    } else {
+                        writeln!(error_writer, "hash: {}: not found", name).ok();
+                        exit_code = -1;
+                    }
+                }
+            }
+        }
+        exit_code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_hash() {
+        assert_eq!(Hash::parse("hash", &[]).unwrap(), Hash::Show);
+        assert_eq!(
+            Hash::parse("hash", &vec_str_to_vec_string::<Vec<_>>(&["-r"])).unwrap(),
+            Hash::Reset
+        );
+        assert_eq!(
+            Hash::parse(
+                "hash",
+                &vec_str_to_vec_string::<Vec<_>>(&["-d", "ls", "cat"])
+            )
+            .unwrap(),
+            Hash::Delete(vec_str_to_vec_string(&["ls", "cat"]))
+        );
+        assert_eq!(
+            Hash::parse(
+                "hash",
+                &vec_str_to_vec_string::<Vec<_>>(&["-p", "/usr/bin/ls", "list"])
+            )
+            .unwrap(),
+            Hash::Set(PathBuf::from("/usr/bin/ls"), vec!["list".to_string()])
+        );
+        assert!(
+            Hash::parse(
+                "hash",
+                &vec_str_to_vec_string::<Vec<_>>(&["-p", "/usr/bin/ls"])
+            )
+            .is_err()
+        );
+        assert!(Hash::parse("hash", &vec_str_to_vec_string::<Vec<_>>(&["-x"])).is_err());
+    }
+}
@@ -1,21 +1,71 @@
-use std::{fs::OpenOptions, io::Write, path::PathBuf, str::FromStr, sync::atomic::Ordering};
-
-use rustyline::history::History as _;
+use std::{
+    env,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::Ordering,
+};
 
 use crate::{
-    RL, Result,
+    HISTORY_FILE, Result,
     builtin::ExitCode,
     command::{Execute, Parse, ParseCommandError},
-    history::{CURRENT_SESSION_HISTORY, LAST_APPEND_INDEX, load_history, save_history},
+    history::{
+        CURRENT_SESSION_HISTORY, EntryMetadata, HistoryEntry, LAST_APPEND_INDEX, LAST_READ_INDEX,
+        append_session_history, delete_history, forget_current_line, history_entries, load_history,
+        load_new_history, replace_history, save_history,
+    },
+    history_expansion::expand_history,
     map_err_to_exit_code,
     redirect::{Reader, Writer},
+    utils::format_local_time,
 };
 
-// -r, -a -w
+// 设置了 HISTTIMEFORMAT 时在命令前显示执行时间，没有时间戳的记录显示 ??
+fn format_history_line(entry: &HistoryEntry, time_format: Option<&str>) -> String {
+    match (time_format, entry.metadata.timestamp) {
+        (Some(format), Some(timestamp)) => {
+            format!("{}{}", format_local_time(format, timestamp), entry.line)
+        }
+        (Some(_), None) => format!("??{}", entry.line),
+        (None, _) => entry.line.clone(),
+    }
+}
+
+// 解析 -d 的参数，可以是单个位置或者 start-end 范围
+fn parse_offset_range(spec: &str) -> Option<(i64, i64)> {
+    if let Ok(offset) = spec.parse() {
+        return Some((offset, offset));
+    }
+    spec.char_indices()
+        .filter(|(idx, c)| *c == '-' && *idx > 0)
+        .find_map(|(idx, _)| Some((spec[..idx].parse().ok()?, spec[idx + 1..].parse().ok()?)))
+}
+
+// 负数从末尾开始计算，-1 表示最后一条记录，返回从 0 开始的下标
+fn resolve_offset(offset: i64, len: usize) -> Option<usize> {
+    let idx = if offset < 0 {
+        len as i64 + offset
+    } else {
+        offset - 1
+    };
+    (0..len as i64).contains(&idx).then_some(idx as usize)
+}
+
+fn is_history_file(file: &Path) -> bool {
+    file == Path::new(HISTORY_FILE.as_str())
+}
+
+// history [n], -c, -d offset, -s args, -p args, -r/-w/-a/-n [file]
 #[derive(Debug, PartialEq, Eq)]
 pub enum History {
     Show(Option<usize>),
+    Clear,
+    Delete(String, i64, i64),
+    Store(String),
+    Print(Vec<String>),
     Read(PathBuf),
+    ReadNew(PathBuf),
     Write(PathBuf),
     Append(PathBuf),
 }
@@ -25,36 +75,69 @@ impl Parse for History {
     where
         Self: std::marker::Sized,
     {
-        if args.len() <= 1 {
-            let show_num = if args.is_empty() {
-                None
-            } else {
-                Some(args[0].parse()?)
-            };
-            Ok(History::Show(show_num))
-        } else if args.len() == 2 {
-            let file = PathBuf::from_str(&args[1])?;
-            let cmd = match args[0].as_str() {
-                "-r" => {
-                    if !file.is_file() {
-                        return Err(format!(
-                            "File {} does not exits or is not a file.",
-                            file.display()
-                        )
-                        .into());
-                    }
-                    History::Read(file)
+        let Some(option) = args.first() else {
+            return Ok(History::Show(None));
+        };
+        let rest = &args[1..];
+        let history_file = || -> Result<PathBuf> {
+            match rest {
+                [] => Ok(PathBuf::from(HISTORY_FILE.as_str())),
+                [file] => Ok(PathBuf::from_str(file)?),
+                _ => Err(ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 2).into()),
+            }
+        };
+        let cmd = match option.as_str() {
+            "-c" if rest.is_empty() => History::Clear,
+            "-c" => {
+                return Err(
+                    ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 1).into(),
+                );
+            }
+            "-d" => match rest {
+                [spec] => {
+                    let (start, end) = parse_offset_range(spec).ok_or_else(|| {
+                        format!("{}: {}: history position out of range", command, spec)
+                    })?;
+                    History::Delete(spec.to_string(), start, end)
                 }
-                "-w" => History::Write(file),
-                "-a" => History::Append(file),
-                arg => {
-                    return Err(format!("unknown parameter: {}", arg).into());
+                [] => return Err(format!("{}: -d: option requires an argument", command).into()),
+                _ => {
+                    return Err(
+                        ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 2).into(),
+                    );
                 }
-            };
-            Ok(cmd)
-        } else {
-            Err(ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 2).into())
-        }
+            },
+            "-s" => History::Store(rest.join(" ")),
+            "-p" => History::Print(rest.to_vec()),
+            "-r" => {
+                let file = history_file()?;
+                if !file.is_file() {
+                    return Err(format!(
+                        "File {} does not exits or is not a file.",
+                        file.display()
+                    )
+                    .into());
+                }
+                History::Read(file)
+            }
+            "-n" => History::ReadNew(history_file()?),
+            "-w" => History::Write(history_file()?),
+            "-a" => History::Append(history_file()?),
+            arg if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("{}: {}: invalid option", command, arg).into());
+            }
+            arg if rest.is_empty() => {
+                History::Show(Some(arg.parse().map_err(|_| {
+                    format!("{}: {}: numeric argument required", command, arg)
+                })?))
+            }
+            _ => {
+                return Err(
+                    ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 1).into(),
+                );
+            }
+        };
+        Ok(cmd)
     }
 }
 
@@ -67,21 +150,80 @@ impl Execute for History {
     ) -> ExitCode {
         match self {
             History::Show(show_num) => {
-                let rl = map_err_to_exit_code!(RL.lock());
-                let history = rl.history();
+                let history = history_entries();
+                let time_format = env::var("HISTTIMEFORMAT").ok();
                 let num = history.len();
                 let length = (num as f64).log10() as usize + 1;
-                let skip_num = show_num.map_or(0, |n| num - n);
+                let skip_num = show_num.map_or(0, |n| num.saturating_sub(n));
                 for (idx, record) in history.iter().enumerate().skip(skip_num) {
                     map_err_to_exit_code!(writeln!(
                         output_writer,
                         "   {:length$}  {}",
                         idx + 1,
-                        record
+                        format_history_line(record, time_format.as_deref())
                     ));
                 }
                 0
             }
+            History::Clear => {
+                replace_history(vec![]);
+                map_err_to_exit_code!(CURRENT_SESSION_HISTORY.lock()).clear();
+                LAST_APPEND_INDEX.store(0, Ordering::Relaxed);
+                0
+            }
+            History::Delete(spec, start, end) => {
+                let len = history_entries().len();
+                let range = resolve_offset(*start, len)
+                    .zip(resolve_offset(*end, len))
+                    .filter(|(start, end)| start <= end);
+                let Some((start, end)) = range else {
+                    writeln!(
+                        error_writer,
+                        "history: {}: history position out of range",
+                        spec
+                    )
+                    .ok();
+                    return -1;
+                };
+                delete_history(start, end);
+                0
+            }
+            History::Store(line) => {
+                if line.is_empty() {
+                    return 0;
+                }
+                let entry = HistoryEntry {
+                    line: line.clone(),
+                    metadata: EntryMetadata::now(),
+                };
+                // 和 bash 一样，用参数替换掉 history -s 这条命令本身
+                forget_current_line();
+                let mut entries = history_entries();
+                let mut session = map_err_to_exit_code!(CURRENT_SESSION_HISTORY.lock());
+                entries.push(entry.clone());
+                session.push(entry);
+                replace_history(entries);
+                0
+            }
+            History::Print(args) => {
+                let history: Vec<String> = history_entries()
+                    .into_iter()
+                    .map(|entry| entry.line)
+                    .collect();
+                for arg in args {
+                    match expand_history(arg, &history) {
+                        Ok(expanded) => {
+                            let line = expanded.map_or(arg.clone(), |expanded| expanded.line);
+                            map_err_to_exit_code!(writeln!(output_writer, "{}", line));
+                        }
+                        Err(err) => {
+                            writeln!(error_writer, "history: {}", err).ok();
+                            return -1;
+                        }
+                    }
+                }
+                0
+            }
             History::Read(file) => {
                 if load_history(file).is_err() {
                     writeln!(error_writer, "Failed to read {}.", file.display()).ok();
@@ -90,31 +232,106 @@ impl Execute for History {
                     0
                 }
             }
+            History::ReadNew(file) => {
+                if load_new_history(file).is_err() {
+                    writeln!(error_writer, "Failed to read {}.", file.display()).ok();
+                    -1
+                } else {
+                    0
+                }
+            }
             History::Write(file) => {
-                if save_history(file, false).is_err() {
+                if save_history(file).is_err() {
                     writeln!(error_writer, "Failed to write {}.", file.display()).ok();
                     -1
                 } else {
+                    if is_history_file(file) {
+                        LAST_READ_INDEX.store(history_entries().len(), Ordering::Relaxed);
+                    }
                     0
                 }
             }
-            History::Append(file) => {
-                if let Ok(mut fp) = OpenOptions::new().append(true).create(true).open(file) {
-                    let current_session_history = CURRENT_SESSION_HISTORY
-                        .lock()
-                        .expect("Failed to get current session history");
-                    let hists_to_save = current_session_history
-                        [LAST_APPEND_INDEX.load(Ordering::Relaxed)..]
-                        .iter()
-                        .fold(String::new(), |acc, hist| acc + hist + "\n");
-                    if fp.write_all(hists_to_save.as_bytes()).is_ok() {
-                        LAST_APPEND_INDEX.store(current_session_history.len(), Ordering::Relaxed);
-                        return 0;
+            History::Append(file) => match append_session_history(file) {
+                Ok(count) => {
+                    // 自己追加的记录不需要再被 history -n 读取
+                    if is_history_file(file) {
+                        LAST_READ_INDEX.fetch_add(count, Ordering::Relaxed);
                     }
+                    0
                 }
-                writeln!(error_writer, "Failed to append {}.", file.display()).ok();
-                -1
-            }
+                Err(_) => {
+                    writeln!(error_writer, "Failed to append {}.", file.display()).ok();
+                    -1
+                }
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    #[test]
+    fn test_format_history_line() {
+        let mut entry = HistoryEntry {
+            line: "ls".to_string(),
+            metadata: EntryMetadata::default(),
+        };
+        assert_eq!(format_history_line(&entry, None), "ls");
+        assert_eq!(format_history_line(&entry, Some("%s ")), "??ls");
+        entry.metadata.timestamp = Some(1700000000);
+        assert_eq!(format_history_line(&entry, Some("%s ")), "1700000000 ls");
+    }
+
+    #[test]
+    fn test_parse_history() {
+        let parse =
+            |args: &[&str]| History::parse("history", &vec_str_to_vec_string::<Vec<_>>(args));
+        assert_eq!(parse(&[]).unwrap(), History::Show(None));
+        assert_eq!(parse(&["5"]).unwrap(), History::Show(Some(5)));
+        assert_eq!(parse(&["-c"]).unwrap(), History::Clear);
+        assert_eq!(
+            parse(&["-d", "-3"]).unwrap(),
+            History::Delete("-3".to_string(), -3, -3)
+        );
+        assert_eq!(
+            parse(&["-d", "5-10"]).unwrap(),
+            History::Delete("5-10".to_string(), 5, 10)
+        );
+        assert_eq!(
+            parse(&["-d", "-5--2"]).unwrap(),
+            History::Delete("-5--2".to_string(), -5, -2)
+        );
+        assert_eq!(
+            parse(&["-s", "echo", "a"]).unwrap(),
+            History::Store("echo a".to_string())
+        );
+        assert_eq!(
+            parse(&["-w"]).unwrap(),
+            History::Write(PathBuf::from(HISTORY_FILE.as_str()))
+        );
+        assert_eq!(
+            parse(&["-a", "/tmp/h"]).unwrap(),
+            History::Append(PathBuf::from("/tmp/h"))
+        );
+        assert!(parse(&["-d"]).is_err());
+        assert!(parse(&["-d", "x"]).is_err());
+        assert!(parse(&["-x"]).is_err());
+        assert!(parse(&["a"]).is_err());
+        assert!(parse(&["-c", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_offset() {
+        assert_eq!(resolve_offset(1, 3), Some(0));
+        assert_eq!(resolve_offset(3, 3), Some(2));
+        assert_eq!(resolve_offset(-1, 3), Some(2));
+        assert_eq!(resolve_offset(-3, 3), Some(0));
+        assert_eq!(resolve_offset(0, 3), None);
+        assert_eq!(resolve_offset(4, 3), None);
+        assert_eq!(resolve_offset(-4, 3), None);
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::Write;
+
+use crate::{
+    Result,
+    arithmetic::evaluate,
+    builtin::ExitCode,
+    command::{Execute, Parse, ParseCommandError},
+    redirect::{Reader, Writer},
+};
+
+// 与 bash 一致: 最后一个表达式的值非 0 时返回 0，否则返回 1
+fn exit_code_of(exprs: &[String], error_writer: &mut Writer) -> ExitCode {
+    let mut value = 0;
+    for expr in exprs {
+        match evaluate(expr) {
+            Ok(result) => value = result,
+            Err(err) => {
+                writeln!(error_writer, "{}", err).ok();
+                return 1;
+            }
+        }
+    }
+    (value == 0) as ExitCode
+}
+
+// let expr...
+#[derive(Debug, PartialEq, Eq)]
+pub struct Let {
+    pub exprs: Vec<String>,
+}
+
+impl Parse for Let {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        if args.is_empty() {
+            return Err(ParseCommandError::LessArgs(command.to_string(), args.to_vec(), 1).into());
+        }
+        Ok(Let {
+            exprs: args.to_vec(),
+        })
+    }
+}
+
+impl Execute for Let {
+    fn execute(
+        &self,
+        _reader: Reader,
+        _output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        exit_code_of(&self.exprs, &mut error_writer)
+    }
+}
+
+// (( expr ))，由 parser 合并为 ["((", expr, "))"]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ArithmeticCommand {
+    pub expr: String,
+}
+
+impl Parse for ArithmeticCommand {
+    fn parse(_command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        match args {
+            [expr, end] if end == "))" => Ok(ArithmeticCommand {
+                expr: expr.to_string(),
+            }),
+            _ => Err("syntax error: unexpected end of file, expected `))'".into()),
+        }
+    }
+}
+
+impl Execute for ArithmeticCommand {
+    fn execute(
+        &self,
+        _reader: Reader,
+        _output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        exit_code_of(std::slice::from_ref(&self.expr), &mut error_writer)
+    }
+}
@@ -1,20 +1,42 @@
-use std::{collections::HashSet, env, io::Write, path::PathBuf};
+use std::{collections::HashSet, io::Write};
 
 use lazy_static::lazy_static;
 
 use crate::{
-    HISTORY_FILE, Result, command::{Execute, Parse, ParseCommandError}, history::save_history, redirect::{Reader, Writer}
+    HISTORY_FILE, Result,
+    command::{Execute, Parse, ParseCommandError},
+    history::append_session_history,
+    redirect::{Reader, Writer},
 };
 
+mod cd;
+mod complete;
+mod dirs;
+mod fc;
+mod hash;
 mod history;
+mod let_;
+mod printf;
+mod test;
 mod type_;
 
+use cd::{Cd, Pwd};
+use complete::Complete;
+use dirs::{Dirs, Popd, Pushd};
+use fc::Fc;
+use hash::Hash;
 use history::History;
+use let_::{ArithmeticCommand, Let};
+use printf::{Printf, expand_escapes};
+use test::{Conditional, Test};
 use type_::Type;
 
 lazy_static! {
-    pub static ref BUILTIN_COMMANDS: HashSet<&'static str> =
-        HashSet::from(["echo", "type", "history", "pwd", "cd", "exit"]);
+    pub static ref BUILTIN_COMMANDS: HashSet<&'static str> = HashSet::from([
+        "echo", "printf", "test", "[", "let", "type", "history", "fc", "complete", "hash", "pwd",
+        "cd", "pushd", "popd", "dirs", "exit",
+    ]);
+    pub static ref SHELL_KEYWORDS: HashSet<&'static str> = HashSet::from(["[[", "]]", "(("]);
 }
 
 pub type ExitCode = i32;
@@ -22,10 +44,21 @@ pub type ExitCode = i32;
 #[derive(Debug, PartialEq, Eq)]
 pub enum BuiltinCommand {
     Echo(String),
+    Printf(Printf),
+    Test(Test),
+    Conditional(Conditional),
+    Let(Let),
+    Arithmetic(ArithmeticCommand),
     Type(Type),
     History(History),
-    Pwd,
-    Cd(String),
+    Fc(Fc),
+    Complete(Complete),
+    Hash(Hash),
+    Pwd(Pwd),
+    Cd(Cd),
+    Pushd(Pushd),
+    Popd(Popd),
+    Dirs(Dirs),
     Exit(ExitCode),
 }
 
@@ -36,39 +69,53 @@ impl Parse for BuiltinCommand {
     {
         let builtin_command = match command {
             "echo" => {
-                let content = if !args.is_empty() {
-                    args.join(" ").to_string()
-                } else {
-                    "\n".to_string()
-                };
-                BuiltinCommand::Echo(content)
-            }
-            "type" => BuiltinCommand::Type(Type::parse(command, args)?),
-            "history" => BuiltinCommand::History(History::parse(command, args)?),
-            "pwd" => {
-                // TODO 能否统一 check arg num 过程？
-                if !args.is_empty() {
-                    return Err(
-                        ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 0).into(),
-                    );
+                // 只有由 n、e、E 组成的参数才被当作选项，比如 echo -x 会原样输出
+                let options_len = args
+                    .iter()
+                    .take_while(|arg| {
+                        arg.len() > 1
+                            && arg.starts_with('-')
+                            && arg[1..].chars().all(|c| matches!(c, 'n' | 'e' | 'E'))
+                    })
+                    .count();
+                let mut newline = true;
+                let mut interpret_escapes = false;
+                for option in args[..options_len].iter().flat_map(|arg| arg[1..].chars()) {
+                    match option {
+                        'n' => newline = false,
+                        'e' => interpret_escapes = true,
+                        _ => interpret_escapes = false,
+                    }
                 }
 
-                BuiltinCommand::Pwd
-            }
-            "cd" => {
-                if args.len() > 1 {
-                    return Err(
-                        ParseCommandError::MoreArgs(command.to_string(), args.to_vec(), 1).into(),
-                    );
+                let mut content = args[options_len..].join(" ");
+                if interpret_escapes {
+                    let (expanded, stop) = expand_escapes(&content, true);
+                    content = expanded;
+                    // \c 之后的内容 (包括换行) 都不输出
+                    newline &= !stop;
                 }
-
-                let target_dir = if !args.is_empty() {
-                    args[0].to_string()
-                } else {
-                    "~".to_string()
-                };
-                BuiltinCommand::Cd(target_dir)
+                if newline {
+                    content.push('\n');
+                }
+                BuiltinCommand::Echo(content)
             }
+            "printf" => BuiltinCommand::Printf(Printf::parse(command, args)?),
+            "test" | "[" => BuiltinCommand::Test(Test::parse(command, args)?),
+            "[[" => BuiltinCommand::Conditional(Conditional::parse(command, args)?),
+            "]]" => return Err("syntax error near unexpected token `]]'".into()),
+            "let" => BuiltinCommand::Let(Let::parse(command, args)?),
+            "((" => BuiltinCommand::Arithmetic(ArithmeticCommand::parse(command, args)?),
+            "type" => BuiltinCommand::Type(Type::parse(command, args)?),
+            "history" => BuiltinCommand::History(History::parse(command, args)?),
+            "fc" => BuiltinCommand::Fc(Fc::parse(command, args)?),
+            "complete" => BuiltinCommand::Complete(Complete::parse(command, args)?),
+            "hash" => BuiltinCommand::Hash(Hash::parse(command, args)?),
+            "pwd" => BuiltinCommand::Pwd(Pwd::parse(command, args)?),
+            "cd" => BuiltinCommand::Cd(Cd::parse(command, args)?),
+            "pushd" => BuiltinCommand::Pushd(Pushd::parse(command, args)?),
+            "popd" => BuiltinCommand::Popd(Popd::parse(command, args)?),
+            "dirs" => BuiltinCommand::Dirs(Dirs::parse(command, args)?),
             "exit" => {
                 if args.len() > 1 {
                     return Err(
@@ -86,54 +133,38 @@ impl Parse for BuiltinCommand {
 }
 
 impl Execute for BuiltinCommand {
-    fn execute(
-        &self,
-        reader: Reader,
-        mut output_writer: Writer,
-        mut error_writer: Writer,
-    ) -> ExitCode {
+    fn execute(&self, reader: Reader, mut output_writer: Writer, error_writer: Writer) -> ExitCode {
         match self {
             BuiltinCommand::Echo(content) => {
-                -(writeln!(output_writer, "{}", content).is_err() as ExitCode)
+                -(write!(output_writer, "{}", content).is_err() as ExitCode)
+            }
+            BuiltinCommand::Printf(printf) => printf.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Test(test) => test.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Conditional(conditional) => {
+                conditional.execute(reader, output_writer, error_writer)
+            }
+            BuiltinCommand::Let(let_) => let_.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Arithmetic(arithmetic) => {
+                arithmetic.execute(reader, output_writer, error_writer)
             }
             BuiltinCommand::Type(ty) => ty.execute(reader, output_writer, error_writer),
             BuiltinCommand::History(hist) => hist.execute(reader, output_writer, error_writer),
-            BuiltinCommand::Pwd => {
-                if let Ok(pwd) = env::current_dir() {
-                    -(writeln!(output_writer, "{}", pwd.display()).is_err() as ExitCode)
-                } else {
-                    let _ = writeln!(error_writer, "invalid directory");
-                    -1
-                }
-            }
-            BuiltinCommand::Cd(target_dir) => {
-                let mut paths: Vec<String> = PathBuf::from(target_dir)
-                    .components()
-                    .map(|p| p.as_os_str().to_string_lossy().to_string())
-                    .collect();
-                //TODO 是否需要检查 paths.is_empty()
-                if paths[0] == "~" {
-                    paths[0] = env::home_dir()
-                        .map_or("".to_string(), |path| path.to_string_lossy().to_string());
-                }
-                let target_dir: PathBuf = paths.iter().collect();
-                if env::set_current_dir(&target_dir).is_err() {
-                    let _ = writeln!(
-                        error_writer,
-                        "cd: {}: No such file or directory",
-                        target_dir.display()
-                    );
-                    -1
-                } else {
-                    0
-                }
+            BuiltinCommand::Fc(fc) => fc.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Complete(complete) => {
+                complete.execute(reader, output_writer, error_writer)
             }
+            BuiltinCommand::Hash(hash) => hash.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Pwd(pwd) => pwd.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Cd(cd) => cd.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Pushd(pushd) => pushd.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Popd(popd) => popd.execute(reader, output_writer, error_writer),
+            BuiltinCommand::Dirs(dirs) => dirs.execute(reader, output_writer, error_writer),
             BuiltinCommand::Exit(exit_code) => {
                 // RL.lock()
                 //     .unwrap()
                 //     .append_history(HISTORY_FILE.as_str())
                 //     .ok();
-                save_history(HISTORY_FILE.as_str(), true).ok();
+                append_session_history(HISTORY_FILE.as_str()).ok();
                 std::process::exit(*exit_code)
             }
         }
@@ -164,7 +195,32 @@ mod tests {
                 &vec_str_to_vec_string::<Vec<_>>(&["abc", "", "123"])
             )
             .unwrap(),
-            BuiltinCommand::Echo("abc  123".to_string())
+            BuiltinCommand::Echo("abc  123\n".to_string())
+        );
+        assert_eq!(
+            BuiltinCommand::parse(
+                "echo",
+                &vec_str_to_vec_string::<Vec<_>>(&["-n", "-e", "a\\tb"])
+            )
+            .unwrap(),
+            BuiltinCommand::Echo("a\tb".to_string())
+        );
+        assert_eq!(
+            BuiltinCommand::parse(
+                "echo",
+                &vec_str_to_vec_string::<Vec<_>>(&["-eE", "a\\tb", "-n"])
+            )
+            .unwrap(),
+            BuiltinCommand::Echo("a\\tb -n\n".to_string())
+        );
+        assert_eq!(
+            BuiltinCommand::parse("echo", &vec_str_to_vec_string::<Vec<_>>(&["-e", "a\\cb"]))
+                .unwrap(),
+            BuiltinCommand::Echo("a".to_string())
+        );
+        assert_eq!(
+            BuiltinCommand::parse("echo", &vec_str_to_vec_string::<Vec<_>>(&["-x", "-"])).unwrap(),
+            BuiltinCommand::Echo("-x -\n".to_string())
         );
     }
 
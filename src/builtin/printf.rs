@@ -0,0 +1,657 @@
+use std::{env, io::Write};
+
+use crate::{
+    Result,
+    builtin::ExitCode,
+    command::{Execute, Parse, ParseCommandError},
+    redirect::{Reader, Writer},
+    utils::is_main_thread,
+};
+
+// 展开反斜杠转义，返回展开后的内容以及是否遇到了 \c (之后的内容全部丢弃)
+// echo -e 和 %b 的八进制写法为 \0nnn，printf 的格式字符串中为 \nnn
+pub fn expand_escapes(s: &str, octal_needs_zero: bool) -> (String, bool) {
+    let chars: Vec<char> = s.chars().collect();
+    let mut expanded = String::new();
+    let mut idx = 0;
+
+    // 读取最多 max_len 个 radix 进制的数字
+    let read_number = |idx: &mut usize, radix: u32, max_len: usize| -> Option<u32> {
+        let start = *idx;
+        while *idx < chars.len() && *idx - start < max_len && chars[*idx].is_digit(radix) {
+            *idx += 1;
+        }
+        let digits: String = chars[start..*idx].iter().collect();
+        u32::from_str_radix(&digits, radix).ok()
+    };
+
+    while idx < chars.len() {
+        if chars[idx] != '\\' || idx + 1 == chars.len() {
+            expanded.push(chars[idx]);
+            idx += 1;
+            continue;
+        }
+        let escape = chars[idx + 1];
+        idx += 2;
+        match escape {
+            'a' => expanded.push('\x07'),
+            'b' => expanded.push('\x08'),
+            'c' => return (expanded, true),
+            'e' | 'E' => expanded.push('\x1b'),
+            'f' => expanded.push('\x0c'),
+            'n' => expanded.push('\n'),
+            'r' => expanded.push('\r'),
+            't' => expanded.push('\t'),
+            'v' => expanded.push('\x0b'),
+            '\\' => expanded.push('\\'),
+            '0' if octal_needs_zero => {
+                let code = read_number(&mut idx, 8, 3).unwrap_or(0);
+                expanded.push(char::from_u32(code).unwrap_or('\0'));
+            }
+            '0'..='7' if !octal_needs_zero => {
+                idx -= 1;
+                let code = read_number(&mut idx, 8, 3).unwrap_or(0);
+                expanded.push(char::from_u32(code).unwrap_or('\0'));
+            }
+            'x' | 'u' | 'U' => {
+                let max_len = match escape {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                match read_number(&mut idx, 16, max_len).and_then(char::from_u32) {
+                    Some(c) => expanded.push(c),
+                    None => {
+                        expanded.push('\\');
+                        expanded.push(escape);
+                    }
+                }
+            }
+            _ => {
+                expanded.push('\\');
+                expanded.push(escape);
+            }
+        }
+    }
+    (expanded, false)
+}
+
+// 与 bash 的 printf %q 一致，输出可以被 shell 重新读取的形式
+pub fn shell_quote(s: &str) -> String {
+    if s.is_empty() {
+        return "''".to_string();
+    }
+    if s.chars().any(|c| c.is_control()) {
+        let mut quoted = "$'".to_string();
+        for c in s.chars() {
+            match c {
+                '\n' => quoted.push_str("\\n"),
+                '\t' => quoted.push_str("\\t"),
+                '\r' => quoted.push_str("\\r"),
+                '\x1b' => quoted.push_str("\\E"),
+                '\'' => quoted.push_str("\\'"),
+                '\\' => quoted.push_str("\\\\"),
+                c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        return quoted;
+    }
+    let mut quoted = String::new();
+    for c in s.chars() {
+        if !c.is_alphanumeric() && !"_@%+=:,./-".contains(c) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+#[derive(Debug, Default)]
+struct FormatSpec {
+    left_align: bool,
+    plus_sign: bool,
+    space_sign: bool,
+    alternate: bool,
+    zero_pad: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl FormatSpec {
+    fn pad(&self, s: String) -> String {
+        let len = s.chars().count();
+        if len >= self.width {
+            return s;
+        }
+        let padding = self.width - len;
+        if self.left_align {
+            s + &" ".repeat(padding)
+        } else if self.zero_pad {
+            // 补零需要放在符号和 0x 前缀之后
+            let prefix_len = if s.starts_with("0x") || s.starts_with("0X") {
+                2
+            } else {
+                s.starts_with(['-', '+', ' ']) as usize
+            };
+            format!(
+                "{}{}{}",
+                &s[..prefix_len],
+                "0".repeat(padding),
+                &s[prefix_len..]
+            )
+        } else {
+            " ".repeat(padding) + &s
+        }
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus_sign {
+            "+"
+        } else if self.space_sign {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    fn format_integer(&self, value: i128, conversion: char) -> String {
+        let magnitude = value.unsigned_abs();
+        let mut digits = match conversion {
+            'x' => format!("{:x}", magnitude),
+            'X' => format!("{:X}", magnitude),
+            'o' => format!("{:o}", magnitude),
+            _ => magnitude.to_string(),
+        };
+        if let Some(precision) = self.precision {
+            if digits.len() < precision {
+                digits = "0".repeat(precision - digits.len()) + &digits;
+            } else if precision == 0 && magnitude == 0 {
+                digits.clear();
+            }
+        }
+        let prefix = match conversion {
+            'x' if self.alternate && magnitude != 0 => "0x",
+            'X' if self.alternate && magnitude != 0 => "0X",
+            'o' if self.alternate && !digits.starts_with('0') => "0",
+            _ => "",
+        };
+        let sign = match conversion {
+            'd' | 'i' => self.sign(value < 0),
+            _ => "",
+        };
+        format!("{}{}{}", sign, prefix, digits)
+    }
+
+    fn format_float(&self, value: f64, conversion: char) -> String {
+        let precision = self.precision.unwrap_or(6);
+        let body = if value.is_infinite() {
+            "inf".to_string()
+        } else if value.is_nan() {
+            "nan".to_string()
+        } else {
+            match conversion.to_ascii_lowercase() {
+                'e' => format_exponent(value.abs(), precision),
+                'g' => {
+                    let precision = precision.max(1);
+                    let exponent = if value == 0.0 {
+                        0
+                    } else {
+                        value.abs().log10().floor() as i32
+                    };
+                    let mut formatted = if exponent < -4 || exponent >= precision as i32 {
+                        format_exponent(value.abs(), precision - 1)
+                    } else {
+                        format!(
+                            "{:.*}",
+                            (precision as i32 - 1 - exponent).max(0) as usize,
+                            value.abs()
+                        )
+                    };
+                    if !self.alternate {
+                        formatted = strip_trailing_zeros(&formatted);
+                    }
+                    formatted
+                }
+                _ => format!("{:.*}", precision, value.abs()),
+            }
+        };
+        let body = if conversion.is_ascii_uppercase() {
+            body.to_uppercase()
+        } else {
+            body
+        };
+        format!(
+            "{}{}",
+            self.sign(value.is_sign_negative() && value != 0.0),
+            body
+        )
+    }
+}
+
+// Rust 的 {:e} 不补齐指数位数，这里按照 C 的格式输出，比如 1.500000e+00
+fn format_exponent(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    format!(
+        "{}e{}{:02}",
+        mantissa,
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+fn strip_trailing_zeros(s: &str) -> String {
+    let (number, exponent) = match s.find('e') {
+        Some(idx) => s.split_at(idx),
+        None => (s, ""),
+    };
+    let number = if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        number
+    };
+    format!("{}{}", number, exponent)
+}
+
+// 数字参数支持十进制、0x 十六进制、0 开头的八进制，以及 'c 表示字符的编码
+fn parse_integer(arg: &str) -> std::result::Result<i128, String> {
+    let trimmed = arg.trim();
+    if let Some(c) = trimmed
+        .strip_prefix('\'')
+        .or_else(|| trimmed.strip_prefix('"'))
+    {
+        return Ok(c.chars().next().map_or(0, |c| c as i128));
+    }
+    if trimmed.is_empty() {
+        return Ok(0);
+    }
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i128::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i128::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    value
+        .map(|value| if negative { -value } else { value })
+        .map_err(|_| format!("{}: invalid number", arg))
+}
+
+fn parse_float(arg: &str) -> std::result::Result<f64, String> {
+    let trimmed = arg.trim();
+    if trimmed.is_empty() {
+        return Ok(0.0);
+    }
+    trimmed
+        .parse()
+        .or_else(|_| parse_integer(arg).map(|value| value as f64))
+        .map_err(|_| format!("{}: invalid number", arg))
+}
+
+// 过大的宽度和精度会一次分配大量内存，超过上限时报错
+const MAX_FIELD_SIZE: usize = 1 << 20;
+
+fn read_digits(chars: &[char], idx: &mut usize) -> String {
+    let digits: String = chars[*idx..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    *idx += digits.len();
+    digits
+}
+
+fn field_size(digits: &str) -> Option<usize> {
+    if digits.is_empty() {
+        return Some(0);
+    }
+    digits.parse().ok().filter(|size| *size <= MAX_FIELD_SIZE)
+}
+
+pub struct PrintfOutput {
+    pub output: String,
+    pub errors: Vec<String>,
+}
+
+// 格式字符串会被重复使用，直到所有参数都被消耗
+pub fn format_printf(format: &str, args: &[String]) -> PrintfOutput {
+    let chars: Vec<char> = format.chars().collect();
+    let mut output = String::new();
+    let mut errors = vec![];
+    let mut arg_idx = 0;
+
+    'outer: loop {
+        let pass_start = arg_idx;
+        let mut idx = 0;
+        let mut literal = String::new();
+        while idx < chars.len() {
+            if chars[idx] != '%' {
+                literal.push(chars[idx]);
+                idx += 1;
+                continue;
+            }
+            let (expanded, stop) = expand_escapes(&literal, false);
+            output.push_str(&expanded);
+            literal.clear();
+            if stop {
+                break 'outer;
+            }
+
+            idx += 1;
+            if chars.get(idx) == Some(&'%') {
+                output.push('%');
+                idx += 1;
+                continue;
+            }
+
+            let mut next_arg = || {
+                let arg = args.get(arg_idx).cloned();
+                arg_idx += 1;
+                arg
+            };
+
+            let mut spec = FormatSpec::default();
+            while let Some(&flag) = chars.get(idx) {
+                match flag {
+                    '-' => spec.left_align = true,
+                    '+' => spec.plus_sign = true,
+                    ' ' => spec.space_sign = true,
+                    '#' => spec.alternate = true,
+                    '0' => spec.zero_pad = true,
+                    _ => break,
+                }
+                idx += 1;
+            }
+            let width = if chars.get(idx) == Some(&'*') {
+                idx += 1;
+                let width = next_arg()
+                    .map_or(Ok(0), |arg| parse_integer(&arg))
+                    .unwrap_or_else(|err| {
+                        errors.push(err);
+                        0
+                    });
+                spec.left_align |= width < 0;
+                width.unsigned_abs().to_string()
+            } else {
+                read_digits(&chars, &mut idx)
+            };
+            let Some(width) = field_size(&width) else {
+                errors.push(format!("{}: invalid field width", width));
+                break 'outer;
+            };
+            spec.width = width;
+            if chars.get(idx) == Some(&'.') {
+                idx += 1;
+                let precision = if chars.get(idx) == Some(&'*') {
+                    idx += 1;
+                    next_arg()
+                        .map_or(Ok(0), |arg| parse_integer(&arg))
+                        .unwrap_or_else(|err| {
+                            errors.push(err);
+                            0
+                        })
+                        .max(0)
+                        .to_string()
+                } else {
+                    read_digits(&chars, &mut idx)
+                };
+                let Some(precision) = field_size(&precision) else {
+                    errors.push(format!("{}: invalid precision", precision));
+                    break 'outer;
+                };
+                spec.precision = Some(precision);
+            }
+
+            let Some(&conversion) = chars.get(idx) else {
+                errors.push("`%': missing format character".to_string());
+                break 'outer;
+            };
+            idx += 1;
+            let arg = next_arg();
+            let formatted = match conversion {
+                's' | 'b' | 'q' => {
+                    let arg = arg.unwrap_or_default();
+                    let mut value = match conversion {
+                        'b' => {
+                            let (expanded, stop) = expand_escapes(&arg, true);
+                            if stop {
+                                output.push_str(&spec.pad(expanded));
+                                break 'outer;
+                            }
+                            expanded
+                        }
+                        'q' => shell_quote(&arg),
+                        _ => arg,
+                    };
+                    if let Some(precision) = spec.precision {
+                        value = value.chars().take(precision).collect();
+                    }
+                    spec.zero_pad = false;
+                    value
+                }
+                'c' => {
+                    spec.zero_pad = false;
+                    arg.and_then(|arg| arg.chars().next())
+                        .map(|c| c.to_string())
+                        .unwrap_or_default()
+                }
+                'd' | 'i' | 'u' | 'x' | 'X' | 'o' => {
+                    let value = arg.map_or(Ok(0), |arg| parse_integer(&arg));
+                    let value = value.unwrap_or_else(|err| {
+                        errors.push(err);
+                        0
+                    });
+                    // 指定精度时忽略 0 标志
+                    spec.zero_pad &= spec.precision.is_none();
+                    // 与 C 一致，负数按照 64 位无符号数处理
+                    let value = if conversion != 'd' && conversion != 'i' && value < 0 {
+                        value as i64 as u64 as i128
+                    } else {
+                        value
+                    };
+                    spec.format_integer(value, conversion)
+                }
+                'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                    let value = arg.map_or(Ok(0.0), |arg| parse_float(&arg));
+                    let value = value.unwrap_or_else(|err| {
+                        errors.push(err);
+                        0.0
+                    });
+                    spec.format_float(value, conversion)
+                }
+                _ => {
+                    errors.push(format!("`{}': invalid format character", conversion));
+                    break 'outer;
+                }
+            };
+            output.push_str(&spec.pad(formatted));
+        }
+        let (expanded, stop) = expand_escapes(&literal, false);
+        output.push_str(&expanded);
+        // 本轮没有消耗参数 (格式中没有转换说明) 时不再重复
+        if stop || arg_idx >= args.len() || arg_idx == pass_start {
+            break;
+        }
+    }
+    PrintfOutput { output, errors }
+}
+
+// printf [-v var] format [arguments]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Printf {
+    pub var: Option<String>,
+    pub format: String,
+    pub args: Vec<String>,
+}
+
+impl Parse for Printf {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let mut args = args;
+        let mut var = None;
+        if args.first().is_some_and(|arg| arg == "-v") {
+            let Some(name) = args.get(1) else {
+                return Err(format!("{}: -v: option requires an argument", command).into());
+            };
+            let is_valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_valid_name {
+                return Err(format!("{}: `{}': not a valid identifier", command, name).into());
+            }
+            var = Some(name.to_string());
+            args = &args[2..];
+        }
+        if args.first().is_some_and(|arg| arg == "--") {
+            args = &args[1..];
+        }
+        let Some(format) = args.first() else {
+            return Err(ParseCommandError::LessArgs(command.to_string(), args.to_vec(), 1).into());
+        };
+        Ok(Printf {
+            var,
+            format: format.to_string(),
+            args: args[1..].to_vec(),
+        })
+    }
+}
+
+// 和 bash 一样，变量的值在第一个 NUL 处截断，环境变量中也无法保存 NUL
+fn variable_value(output: &str) -> &str {
+    output.split('\0').next().unwrap_or_default()
+}
+
+impl Execute for Printf {
+    fn execute(
+        &self,
+        _reader: Reader,
+        mut output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let PrintfOutput { output, errors } = format_printf(&self.format, &self.args);
+        for err in &errors {
+            writeln!(error_writer, "printf: {}", err).ok();
+        }
+        if let Some(var) = &self.var {
+            // 管道线程中不修改环境变量
+            if is_main_thread() {
+                unsafe { env::set_var(var, variable_value(&output)) };
+            }
+        } else if write!(output_writer, "{}", output).is_err() {
+            return -1;
+        }
+        if errors.is_empty() { 0 } else { -1 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    fn printf(format: &str, args: &[&str]) -> String {
+        format_printf(format, &vec_str_to_vec_string::<Vec<_>>(args)).output
+    }
+
+    #[test]
+    fn test_expand_escapes() {
+        assert_eq!(
+            expand_escapes("a\\tb\\n\\0101\\x41\\u00e9\\q", true),
+            ("a\tb\nAAé\\q".to_string(), false)
+        );
+        assert_eq!(expand_escapes("\\101", false), ("A".to_string(), false));
+        assert_eq!(expand_escapes("ab\\ccd", true), ("ab".to_string(), true));
+    }
+
+    #[test]
+    fn test_format_printf() {
+        assert_eq!(printf("%s-%s\\n", &["a", "b", "c"]), "a-b\nc-\n");
+        assert_eq!(
+            printf("[%5s|%-5s|%.2s]", &["ab", "cd", "xyz"]),
+            "[   ab|cd   |xy]"
+        );
+        assert_eq!(
+            printf(
+                "%d %i %05d %+d %x %X %#o",
+                &["42", "-7", "-42", "3", "255", "255", "8"]
+            ),
+            "42 -7 -0042 +3 ff FF 010"
+        );
+        assert_eq!(
+            printf("%*d|%-*d|%.*f", &["4", "7", "3", "1", "2", "3.14159"]),
+            "   7|1  |3.14"
+        );
+        assert_eq!(
+            printf(
+                "%f %.1e %E %g %g",
+                &["1.5", "12345", "0.00012", "0.0001", "123456789"]
+            ),
+            "1.500000 1.2e+04 1.200000E-04 0.0001 1.23457e+08"
+        );
+        assert_eq!(printf("%c%c %d", &["hello", "x", "'A"]), "hx 65");
+        assert_eq!(
+            printf("%b|%q|%q", &["a\\tb", "a b'c", ""]),
+            "a\tb|a\\ b\\'c|''"
+        );
+        assert_eq!(printf("%%|no args\\n", &["ignored"]), "%|no args\n");
+        let result = format_printf("%d", &vec_str_to_vec_string::<Vec<_>>(&["abc"]));
+        assert_eq!(result.output, "0");
+        assert_eq!(result.errors, vec!["abc: invalid number"]);
+
+        let errors = |format: &str, args: &[&str]| {
+            format_printf(format, &vec_str_to_vec_string::<Vec<_>>(args)).errors
+        };
+        assert_eq!(
+            errors("%99999999999999999999d", &["1"]),
+            vec!["99999999999999999999: invalid field width"]
+        );
+        assert_eq!(
+            errors("%*d", &["-9223372036854775808", "1"]),
+            vec!["9223372036854775808: invalid field width"]
+        );
+        assert_eq!(
+            errors("%.*f", &["99999999", "1"]),
+            vec!["99999999: invalid precision"]
+        );
+        assert_eq!(printf("%-*d|", &["-3", "1"]), "1  |");
+    }
+
+    #[test]
+    fn test_parse_printf() {
+        assert_eq!(
+            Printf::parse(
+                "printf",
+                &vec_str_to_vec_string::<Vec<_>>(&["-v", "out", "%s", "a"])
+            )
+            .unwrap(),
+            Printf {
+                var: Some("out".to_string()),
+                format: "%s".to_string(),
+                args: vec!["a".to_string()]
+            }
+        );
+        assert!(Printf::parse("printf", &[]).is_err());
+        assert_eq!(variable_value(&printf("a\\0b", &[])), "a");
+        assert_eq!(variable_value(&printf("%s", &["ab"])), "ab");
+        assert!(
+            Printf::parse(
+                "printf",
+                &vec_str_to_vec_string::<Vec<_>>(&["-v", "1x", "%s"])
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use std::{
+    cmp::Ordering,
+    env, fs,
+    io::Write,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::Path,
+};
+
+use is_executable::IsExecutable;
+use regex::Regex;
+
+use crate::{
+    Result,
+    builtin::ExitCode,
+    command::{Execute, Parse},
+    redirect::{Reader, Writer},
+    utils::{glob_match, is_main_thread},
+};
+
+const UNARY_OPERATORS: [&str; 17] = [
+    "-e", "-f", "-d", "-r", "-w", "-x", "-s", "-L", "-h", "-b", "-c", "-p", "-S", "-z", "-n", "-a",
+    "-t",
+];
+
+const BINARY_OPERATORS: [&str; 14] = [
+    "=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef",
+];
+
+fn file_test(op: &str, path: &str) -> bool {
+    let path = Path::new(path);
+    match op {
+        "-L" | "-h" => fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink()),
+        _ => {
+            let Ok(meta) = fs::metadata(path) else {
+                return false;
+            };
+            match op {
+                "-e" | "-a" => true,
+                "-f" => meta.is_file(),
+                "-d" => meta.is_dir(),
+                "-s" => meta.len() > 0,
+                "-b" => meta.file_type().is_block_device(),
+                "-c" => meta.file_type().is_char_device(),
+                "-p" => meta.file_type().is_fifo(),
+                "-S" => meta.file_type().is_socket(),
+                "-x" => path.is_executable(),
+                "-r" if meta.is_dir() => fs::read_dir(path).is_ok(),
+                "-r" => fs::File::open(path).is_ok(),
+                "-w" if meta.is_dir() => !meta.permissions().readonly(),
+                "-w" => fs::OpenOptions::new().append(true).open(path).is_ok(),
+                _ => false,
+            }
+        }
+    }
+}
+
+fn parse_integer(s: &str) -> std::result::Result<i64, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("{}: integer expression expected", s))
+}
+
+fn compare_mtime(lhs: &str, rhs: &str) -> Option<Ordering> {
+    let lhs = fs::metadata(lhs).and_then(|meta| meta.modified()).ok();
+    let rhs = fs::metadata(rhs).and_then(|meta| meta.modified()).ok();
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => Some(lhs.cmp(&rhs)),
+        (Some(_), None) => Some(Ordering::Greater),
+        (None, Some(_)) => Some(Ordering::Less),
+        (None, None) => None,
+    }
+}
+
+// test/[ 和 [[ ]] 共用的表达式求值，extended 表示 [[ ]] 的语法
+struct Evaluator<'a> {
+    tokens: &'a [String],
+    pos: usize,
+    extended: bool,
+    // && || 短路时仍然需要解析右侧的表达式，但不能产生副作用
+    skip: bool,
+    // 最后一次执行的 =~ 的匹配分组，没有匹配时为空
+    rematch: Option<Vec<String>>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(tokens: &'a [String], extended: bool) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            extended,
+            skip: false,
+            rematch: None,
+        }
+    }
+
+    fn peek(&self, offset: usize) -> Option<&'a str> {
+        self.tokens
+            .get(self.pos + offset)
+            .map(|token| token.as_str())
+    }
+
+    fn or_operator(&self) -> &'static str {
+        if self.extended { "||" } else { "-o" }
+    }
+
+    fn and_operator(&self) -> &'static str {
+        if self.extended { "&&" } else { "-a" }
+    }
+
+    fn evaluate(&mut self) -> std::result::Result<bool, String> {
+        if self.tokens.is_empty() {
+            return if self.extended {
+                Err("syntax error in conditional expression".to_string())
+            } else {
+                Ok(false)
+            };
+        }
+        let result = self.or_expr()?;
+        match self.peek(0) {
+            None => Ok(result),
+            Some(token) if self.extended => Err(format!(
+                "syntax error in conditional expression near `{}'",
+                token
+            )),
+            Some(_) => Err("too many arguments".to_string()),
+        }
+    }
+
+    fn or_expr(&mut self) -> std::result::Result<bool, String> {
+        let mut result = self.and_expr()?;
+        while self.peek(0) == Some(self.or_operator()) {
+            self.pos += 1;
+            let rhs = self.skipping(result, Self::and_expr)?;
+            result = result || rhs;
+        }
+        Ok(result)
+    }
+
+    fn and_expr(&mut self) -> std::result::Result<bool, String> {
+        let mut result = self.not_expr()?;
+        while self.peek(0) == Some(self.and_operator()) {
+            self.pos += 1;
+            let rhs = self.skipping(!result, Self::not_expr)?;
+            result = result && rhs;
+        }
+        Ok(result)
+    }
+
+    fn skipping(
+        &mut self,
+        skip: bool,
+        expr: fn(&mut Self) -> std::result::Result<bool, String>,
+    ) -> std::result::Result<bool, String> {
+        let outer = self.skip;
+        self.skip |= skip;
+        let result = expr(self);
+        self.skip = outer;
+        result
+    }
+
+    fn not_expr(&mut self) -> std::result::Result<bool, String> {
+        // 单独的 ! 按照普通字符串处理
+        if self.peek(0) == Some("!") && self.peek(1).is_some() {
+            self.pos += 1;
+            return Ok(!self.not_expr()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> std::result::Result<bool, String> {
+        let Some(token) = self.peek(0) else {
+            return Err("argument expected".to_string());
+        };
+
+        // 优先按照二元表达式处理，比如 [ -f = -f ]
+        if let (Some(op), Some(rhs)) = (self.peek(1), self.peek(2))
+            && (BINARY_OPERATORS.contains(&op) || (self.extended && op == "=~"))
+        {
+            self.pos += 3;
+            return self.binary(token, op, rhs);
+        }
+
+        if token == "(" {
+            self.pos += 1;
+            let result = self.or_expr()?;
+            if self.peek(0) != Some(")") {
+                return Err("`)' expected".to_string());
+            }
+            self.pos += 1;
+            return Ok(result);
+        }
+
+        if UNARY_OPERATORS.contains(&token)
+            && let Some(operand) = self.peek(1)
+        {
+            self.pos += 2;
+            return Ok(match token {
+                "-z" => operand.is_empty(),
+                "-n" => !operand.is_empty(),
+                "-t" => operand.parse::<i32>().is_ok_and(is_terminal_fd),
+                op => file_test(op, operand),
+            });
+        }
+
+        self.pos += 1;
+        Ok(!token.is_empty())
+    }
+
+    fn binary(&mut self, lhs: &str, op: &str, rhs: &str) -> std::result::Result<bool, String> {
+        let result = match op {
+            "=~" => {
+                let re =
+                    Regex::new(rhs).map_err(|_| format!("{}: invalid regular expression", rhs))?;
+                let groups: Vec<String> = re
+                    .captures(lhs)
+                    .map(|caps| {
+                        caps.iter()
+                            .map(|group| group.map_or(String::new(), |m| m.as_str().to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let matched = !groups.is_empty();
+                if !self.skip {
+                    self.rematch = Some(groups);
+                }
+                matched
+            }
+            "=" | "==" if self.extended => glob_match(rhs, lhs),
+            "!=" if self.extended => !glob_match(rhs, lhs),
+            "=" | "==" => lhs == rhs,
+            "!=" => lhs != rhs,
+            "<" => lhs < rhs,
+            ">" => lhs > rhs,
+            "-nt" => compare_mtime(lhs, rhs) == Some(Ordering::Greater),
+            "-ot" => compare_mtime(lhs, rhs) == Some(Ordering::Less),
+            "-ef" => match (fs::metadata(lhs), fs::metadata(rhs)) {
+                (Ok(lhs), Ok(rhs)) => lhs.dev() == rhs.dev() && lhs.ino() == rhs.ino(),
+                _ => false,
+            },
+            _ => {
+                let (lhs, rhs) = (parse_integer(lhs)?, parse_integer(rhs)?);
+                match op {
+                    "-eq" => lhs == rhs,
+                    "-ne" => lhs != rhs,
+                    "-lt" => lhs < rhs,
+                    "-le" => lhs <= rhs,
+                    "-gt" => lhs > rhs,
+                    "-ge" => lhs >= rhs,
+                    _ => unreachable!(),
+                }
+            }
+        };
+        Ok(result)
+    }
+}
+
+// 只判断 0、1、2 三个标准描述符
+fn is_terminal_fd(fd: i32) -> bool {
+    use std::io::IsTerminal;
+    match fd {
+        0 => std::io::stdin().is_terminal(),
+        1 => std::io::stdout().is_terminal(),
+        2 => std::io::stderr().is_terminal(),
+        _ => false,
+    }
+}
+
+fn exit_code_of(
+    command: &str,
+    result: std::result::Result<bool, String>,
+    error_writer: &mut Writer,
+) -> ExitCode {
+    match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) => {
+            writeln!(error_writer, "{}: {}", command, err).ok();
+            2
+        }
+    }
+}
+
+// test expr 或者 [ expr ]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Test {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl Parse for Test {
+    fn parse(command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        let args = if command == "[" {
+            match args.split_last() {
+                Some((last, args)) if last == "]" => args,
+                _ => return Err("[: missing `]'".into()),
+            }
+        } else {
+            args
+        };
+        Ok(Test {
+            command: command.to_string(),
+            args: args.to_vec(),
+        })
+    }
+}
+
+impl Execute for Test {
+    fn execute(
+        &self,
+        _reader: Reader,
+        _output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let result = Evaluator::new(&self.args, false).evaluate();
+        exit_code_of(&self.command, result, &mut error_writer)
+    }
+}
+
+// [[ expr ]]，支持 && || 以及 == 通配符匹配和 =~ 正则匹配
+#[derive(Debug, PartialEq, Eq)]
+pub struct Conditional {
+    pub args: Vec<String>,
+}
+
+impl Parse for Conditional {
+    fn parse(_command: &str, args: &[String]) -> Result<Self>
+    where
+        Self: std::marker::Sized,
+    {
+        match args.split_last() {
+            Some((last, args)) if last == "]]" => Ok(Conditional {
+                args: args.to_vec(),
+            }),
+            _ => Err("syntax error: unexpected end of file, expected `]]'".into()),
+        }
+    }
+}
+
+impl Conditional {
+    // 返回结果以及执行过的 =~ 的匹配分组
+    fn evaluate(&self) -> (std::result::Result<bool, String>, Option<Vec<String>>) {
+        let mut evaluator = Evaluator::new(&self.args, true);
+        let result = evaluator.evaluate();
+        (result, evaluator.rematch)
+    }
+}
+
+// [[ str =~ regex ]] 的匹配结果，没有数组变量，所以整体匹配写入 BASH_REMATCH，
+// 各个分组写入 BASH_REMATCH_1 ... BASH_REMATCH_N，没有匹配时全部删除
+fn export_rematch(groups: &[String]) {
+    for (name, _) in env::vars().filter(|(name, _)| name.starts_with("BASH_REMATCH_")) {
+        unsafe { env::remove_var(name) };
+    }
+    let Some((matched, captures)) = groups.split_first() else {
+        unsafe { env::remove_var("BASH_REMATCH") };
+        return;
+    };
+    unsafe { env::set_var("BASH_REMATCH", matched) };
+    for (idx, group) in captures.iter().enumerate() {
+        unsafe { env::set_var(format!("BASH_REMATCH_{}", idx + 1), group) };
+    }
+}
+
+impl Execute for Conditional {
+    fn execute(
+        &self,
+        _reader: Reader,
+        _output_writer: Writer,
+        mut error_writer: Writer,
+    ) -> ExitCode {
+        let (result, rematch) = self.evaluate();
+        // 管道线程中不修改环境变量
+        if let Some(groups) = rematch
+            && is_main_thread()
+        {
+            export_rematch(&groups);
+        }
+        exit_code_of("[[", result, &mut error_writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::{glob_to_regex, vec_str_to_vec_string};
+
+    use super::*;
+
+    fn test(args: &[&str]) -> std::result::Result<bool, String> {
+        Evaluator::new(&vec_str_to_vec_string::<Vec<_>>(args), false).evaluate()
+    }
+
+    fn conditional(args: &[&str]) -> std::result::Result<bool, String> {
+        Conditional {
+            args: vec_str_to_vec_string(args),
+        }
+        .evaluate()
+        .0
+    }
+
+    #[test]
+    fn test_evaluate_test() {
+        assert_eq!(test(&[]), Ok(false));
+        assert_eq!(test(&["abc"]), Ok(true));
+        assert_eq!(test(&["-n"]), Ok(true));
+        assert_eq!(test(&["-z", ""]), Ok(true));
+        assert_eq!(test(&["!", "-z", "x"]), Ok(true));
+        assert_eq!(test(&["-d", "/tmp", "-a", "-f", "/tmp"]), Ok(false));
+        assert_eq!(test(&["-d", "/tmp", "-o", "-f", "/tmp"]), Ok(true));
+        assert_eq!(
+            test(&["(", "1", "-lt", "2", ")", "-a", "b", "=", "b"]),
+            Ok(true)
+        );
+        assert_eq!(test(&["-f", "=", "-f"]), Ok(true));
+        assert_eq!(test(&["10", "-gt", "9"]), Ok(true));
+        assert_eq!(test(&["a", "<", "b"]), Ok(true));
+        assert!(test(&["a", "-eq", "1"]).is_err());
+        assert!(test(&["a", "b"]).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_conditional() {
+        assert_eq!(conditional(&["abc", "==", "a*"]), Ok(true));
+        assert_eq!(conditional(&["abc", "!=", "a?c"]), Ok(false));
+        assert_eq!(conditional(&["a", "==", "b", "||", "-d", "/"]), Ok(true));
+        assert_eq!(
+            conditional(&["!", "(", "1", "-eq", "1", "&&", "x", ")"]),
+            Ok(false)
+        );
+        assert_eq!(conditional(&["a*c", "==", "a\\*c"]), Ok(true));
+        assert_eq!(conditional(&["abc", "==", "a\\*c"]), Ok(false));
+        assert_eq!(
+            conditional(&["release-1.23", "=~", "([0-9]+)\\.([0-9]+)"]),
+            Ok(true)
+        );
+
+        let rematch = |args: &[&str]| {
+            Conditional {
+                args: vec_str_to_vec_string(args),
+            }
+            .evaluate()
+            .1
+        };
+        assert_eq!(
+            rematch(&["release-1.23", "=~", "([0-9]+)\\.([0-9]+)"]),
+            Some(vec_str_to_vec_string(&["1.23", "1", "23"]))
+        );
+        // 短路时不执行右侧的 =~
+        assert_eq!(rematch(&["a", "==", "b", "&&", "xy", "=~", "(y)"]), None);
+        assert_eq!(
+            rematch(&["ab", "=~", "(a)", "||", "xy", "=~", "(y)"]),
+            Some(vec_str_to_vec_string(&["a", "a"]))
+        );
+        assert_eq!(rematch(&["ab", "=~", "z"]), Some(vec![]));
+
+        export_rematch(&vec_str_to_vec_string::<Vec<_>>(&["ab", "a", "b"]));
+        assert_eq!(env::var("BASH_REMATCH").as_deref(), Ok("ab"));
+        assert_eq!(env::var("BASH_REMATCH_2").as_deref(), Ok("b"));
+        export_rematch(&vec_str_to_vec_string::<Vec<_>>(&["x", "x"]));
+        assert_eq!(env::var("BASH_REMATCH_1").as_deref(), Ok("x"));
+        assert!(env::var("BASH_REMATCH_2").is_err());
+        export_rematch(&[]);
+        assert!(env::var("BASH_REMATCH").is_err());
+        assert!(env::var("BASH_REMATCH_1").is_err());
+        assert!(conditional(&["a", "=~", "("]).is_err());
+        assert!(conditional(&[]).is_err());
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!(glob_to_regex("a*b?.txt"), "^a.*b.\\.txt$");
+        assert_eq!(glob_to_regex("[!a-c]x["), "^[^a-c]x\\[$");
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("\\*.rs", "main.rs"));
+    }
+}
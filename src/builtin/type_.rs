@@ -2,9 +2,10 @@ use std::io::Write;
 
 use crate::{
     Result,
-    builtin::{BUILTIN_COMMANDS, ExitCode},
+    builtin::{BUILTIN_COMMANDS, ExitCode, SHELL_KEYWORDS},
     command::{Execute, Parse, ParseCommandError},
-    executable::find_in_path,
+    executable::{HASHED_COMMANDS, find_in_path},
+    map_err_to_exit_code,
     redirect::{Reader, Writer},
 };
 
@@ -40,8 +41,15 @@ impl Execute for Type {
         _error_writer: Writer,
     ) -> ExitCode {
         for cmd in &self.commands {
-            let exec_res = if BUILTIN_COMMANDS.contains(cmd.as_str()) {
+            let hashed_path = map_err_to_exit_code!(HASHED_COMMANDS.lock())
+                .get(cmd)
+                .map(|hashed| hashed.path.clone());
+            let exec_res = if SHELL_KEYWORDS.contains(cmd.as_str()) {
+                writeln!(output_writer, "{} is a shell keyword", cmd)
+            } else if BUILTIN_COMMANDS.contains(cmd.as_str()) {
                 writeln!(output_writer, "{} is a shell builtin", cmd)
+            } else if let Some(path) = hashed_path {
+                writeln!(output_writer, "{} is hashed ({})", cmd, path.display())
             } else if let Some(path) = find_in_path(cmd) {
                 writeln!(output_writer, "{} is {}", cmd, path.display())
             } else {
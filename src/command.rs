@@ -1,12 +1,20 @@
-use std::{fmt::Display, io::Write};
+use std::{env, fmt::Display, io::Write, sync::atomic::AtomicI32};
 
 use crate::{
     Result,
-    builtin::{BUILTIN_COMMANDS, BuiltinCommand, ExitCode},
+    builtin::{BUILTIN_COMMANDS, BuiltinCommand, ExitCode, SHELL_KEYWORDS},
+    command_index::suggest_commands,
     executable::Executable,
     redirect::{Reader, Writer},
 };
 
+// 最近一条前台命令的退出码
+pub static LAST_EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+// 找不到命令时调用的外部程序，参数为原命令及其参数。目前不支持 shell 函数，
+// 所以不使用 bash 的 command_not_found_handle，而是通过这个变量指定程序的名字或路径
+const COMMAND_NOT_FOUND_HANDLER: &str = "COMMAND_NOT_FOUND_HANDLER";
+
 pub trait Execute {
     fn execute(&self, reader: Reader, output_writer: Writer, error_writer: Writer) -> ExitCode;
 }
@@ -34,7 +42,7 @@ impl Parse for Command {
     {
         let command = if command.is_empty() {
             Command::Empty
-        } else if BUILTIN_COMMANDS.contains(command) {
+        } else if BUILTIN_COMMANDS.contains(command) || SHELL_KEYWORDS.contains(command) {
             Command::BuiltinCommand(BuiltinCommand::parse(command, args)?)
         } else if let Ok(exec) = Executable::parse(command, args) {
             Command::Executable(exec)
@@ -46,17 +54,14 @@ impl Parse for Command {
 }
 
 impl Execute for Command {
-    fn execute(&self, reader: Reader, output_writer: Writer, mut error_writer: Writer) -> ExitCode {
+    fn execute(&self, reader: Reader, output_writer: Writer, error_writer: Writer) -> ExitCode {
         match self {
             Command::Empty => 0,
             Command::BuiltinCommand(builtin_command) => {
                 builtin_command.execute(reader, output_writer, error_writer)
             }
             Command::Executable(exec) => exec.execute(reader, output_writer, error_writer),
-            Command::Unknown(unknown) => {
-                let _ = writeln!(error_writer, "{}: command not found", unknown.command);
-                0
-            }
+            Command::Unknown(unknown) => unknown.execute(reader, output_writer, error_writer),
         }
     }
 }
@@ -73,6 +78,28 @@ impl UnknownCommand {
     }
 }
 
+impl Execute for UnknownCommand {
+    fn execute(&self, reader: Reader, output_writer: Writer, mut error_writer: Writer) -> ExitCode {
+        let handler_args: Args = [self.command.clone()]
+            .into_iter()
+            .chain(self.args.iter().cloned())
+            .collect();
+        if let Ok(handler) = env::var(COMMAND_NOT_FOUND_HANDLER)
+            && !handler.is_empty()
+            && let Ok(handler) = Executable::parse(&handler, &handler_args)
+        {
+            return handler.execute(reader, output_writer, error_writer);
+        }
+
+        let _ = writeln!(error_writer, "{}: command not found", self.command);
+        let suggestions = suggest_commands(&self.command);
+        if !suggestions.is_empty() {
+            let _ = writeln!(error_writer, "did you mean: {}", suggestions.join(", "));
+        }
+        127
+    }
+}
+
 //TODO 更完善的 parse Error
 #[derive(Debug, PartialEq, Eq)]
 pub enum ParseCommandError {
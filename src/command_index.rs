@@ -0,0 +1,314 @@
+use std::{cmp::Reverse, collections::HashMap, fs, path::PathBuf, sync::RwLock, time::SystemTime};
+
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use lazy_static::lazy_static;
+use radix_trie::{Trie, TrieCommon};
+
+use crate::{
+    builtin::BUILTIN_COMMANDS,
+    executable::load_paths,
+    utils::{edit_distance, get_executables_from_dir},
+};
+
+lazy_static! {
+    // 补全、命令建议等共用的命令名索引
+    pub static ref SUPPORT_COMMANDS: RwLock<CommandIndex> = RwLock::new(CommandIndex::new());
+    static ref MATCHER: SkimMatcherV2 = SkimMatcherV2::default().respect_case();
+}
+
+struct IndexedDir {
+    mtime: Option<SystemTime>,
+    commands: Vec<String>,
+}
+
+pub struct CommandIndex {
+    // 命令名 -> 提供该命令的来源数量 (builtin 或 PATH 中的目录)
+    trie: Trie<String, usize>,
+    dirs: HashMap<PathBuf, IndexedDir>,
+}
+
+impl CommandIndex {
+    pub fn new() -> Self {
+        let mut index = Self {
+            trie: Trie::new(),
+            dirs: HashMap::new(),
+        };
+        for cmd in BUILTIN_COMMANDS.iter() {
+            index.add(cmd.to_string());
+        }
+        index.refresh();
+        index
+    }
+
+    fn add(&mut self, cmd: String) {
+        if let Some(count) = self.trie.get_mut(&cmd) {
+            *count += 1;
+        } else {
+            self.trie.insert(cmd, 1);
+        }
+    }
+
+    fn remove(&mut self, cmd: &str) {
+        if let Some(count) = self.trie.get_mut(cmd) {
+            *count -= 1;
+            if *count == 0 {
+                self.trie.remove(cmd);
+            }
+        }
+    }
+
+    fn scan_dir(&mut self, dir: PathBuf, mtime: Option<SystemTime>) {
+        let commands: Vec<String> = get_executables_from_dir(&dir)
+            .iter()
+            .filter_map(|exec| exec.file_name().and_then(|basename| basename.to_str()))
+            .map(|exec| exec.to_string())
+            .collect();
+        for cmd in &commands {
+            self.add(cmd.clone());
+        }
+        self.dirs.insert(dir, IndexedDir { mtime, commands });
+    }
+
+    fn drop_dir(&mut self, dir: &PathBuf) {
+        if let Some(indexed) = self.dirs.remove(dir) {
+            for cmd in &indexed.commands {
+                self.remove(cmd);
+            }
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        self.refresh_dirs(load_paths());
+    }
+
+    // 只重新扫描 mtime 发生变化的目录，并删除已经不在 PATH 中的目录
+    fn refresh_dirs(&mut self, paths: Vec<PathBuf>) {
+        let removed_dirs: Vec<PathBuf> = self
+            .dirs
+            .keys()
+            .filter(|dir| !paths.contains(dir))
+            .cloned()
+            .collect();
+        for dir in &removed_dirs {
+            self.drop_dir(dir);
+        }
+
+        for dir in paths {
+            let mtime = fs::metadata(&dir).and_then(|meta| meta.modified()).ok();
+            if self
+                .dirs
+                .get(&dir)
+                .is_some_and(|indexed| indexed.mtime == mtime)
+            {
+                continue;
+            }
+            self.drop_dir(&dir);
+            self.scan_dir(dir, mtime);
+        }
+    }
+
+    pub fn rebuild(&mut self) {
+        *self = CommandIndex::new();
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.trie.keys()
+    }
+}
+
+pub fn refresh_support_commands() {
+    SUPPORT_COMMANDS
+        .write()
+        .unwrap_or_else(|err| err.into_inner())
+        .refresh();
+}
+
+pub fn is_indexed_command(command: &str) -> bool {
+    SUPPORT_COMMANDS
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .trie
+        .get(command)
+        .is_some()
+}
+
+pub fn prefix_matches(prefix: &str) -> Vec<String> {
+    SUPPORT_COMMANDS
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .trie
+        .get_raw_descendant(prefix)
+        .map(|sub_trie| {
+            // 无需排序，trie 中取出来之后就是按字典序排好序的
+            sub_trie
+                .keys()
+                .filter(|cmd| cmd.starts_with(prefix))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn fuzzy_matches(pattern: &str, frequency: &HashMap<String, usize>) -> Vec<String> {
+    let index = SUPPORT_COMMANDS
+        .read()
+        .unwrap_or_else(|err| err.into_inner());
+    rank_commands(pattern, index.keys().map(|cmd| cmd.as_str()), frequency)
+}
+
+// 命令找不到时给出的建议，只考虑拼写错误
+pub fn suggest_commands(command: &str) -> Vec<String> {
+    let index = SUPPORT_COMMANDS
+        .read()
+        .unwrap_or_else(|err| err.into_inner());
+    closest_commands(command, index.keys().map(|cmd| cmd.as_str()))
+}
+
+fn closest_commands<'a>(command: &str, commands: impl Iterator<Item = &'a str>) -> Vec<String> {
+    const MAX_SUGGESTIONS: usize = 3;
+    let max_distance = match command.chars().count() {
+        0..=2 => return vec![],
+        3..=5 => 1,
+        _ => 2,
+    };
+    let mut matched: Vec<(usize, &str)> = commands
+        .map(|cmd| (edit_distance(command, cmd), cmd))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matched.sort();
+    matched
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, cmd)| cmd.to_string())
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchKind {
+    Prefix,
+    Typo,
+    Subsequence,
+}
+
+// 排序依次考虑: 匹配方式，历史记录中出现的次数，模糊匹配得分
+fn rank_commands<'a>(
+    pattern: &str,
+    commands: impl Iterator<Item = &'a str>,
+    frequency: &HashMap<String, usize>,
+) -> Vec<String> {
+    let max_distance = match pattern.chars().count() {
+        0..=2 => 0,
+        3..=4 => 1,
+        _ => 2,
+    };
+    let mut matched: Vec<(MatchKind, Reverse<usize>, Reverse<i64>, &str)> = commands
+        .filter_map(|cmd| {
+            // 允许输入错误，比如 gti -> git，只和相同长度的前缀比较
+            let cmd_prefix: String = cmd.chars().take(pattern.chars().count()).collect();
+            let distance = edit_distance(pattern, &cmd_prefix).min(edit_distance(pattern, cmd));
+            let (kind, score) = if cmd.starts_with(pattern) {
+                (MatchKind::Prefix, 0)
+            } else if distance <= max_distance {
+                (MatchKind::Typo, -(distance as i64))
+            } else {
+                (MatchKind::Subsequence, MATCHER.fuzzy_match(cmd, pattern)?)
+            };
+            let count = frequency.get(cmd).copied().unwrap_or(0);
+            Some((kind, Reverse(count), Reverse(score), cmd))
+        })
+        .collect();
+    matched.sort();
+    matched
+        .into_iter()
+        .map(|(_, _, _, cmd)| cmd.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    fn create_executable(path: &std::path::Path) {
+        fs::write(path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_refresh_dirs() {
+        let dir_a = std::env::temp_dir().join("test_refresh_dirs_a");
+        let dir_b = std::env::temp_dir().join("test_refresh_dirs_b");
+        for dir in [&dir_a, &dir_b] {
+            fs::remove_dir_all(dir).ok();
+            fs::create_dir_all(dir).unwrap();
+        }
+        create_executable(&dir_a.join("tool_a"));
+        create_executable(&dir_a.join("tool_shared"));
+        create_executable(&dir_b.join("tool_shared"));
+
+        let mut index = CommandIndex {
+            trie: Trie::new(),
+            dirs: HashMap::new(),
+        };
+        let names = |index: &CommandIndex| {
+            let mut names: Vec<String> = index.keys().cloned().collect();
+            names.sort();
+            names
+        };
+
+        index.refresh_dirs(vec![dir_a.clone(), dir_b.clone()]);
+        assert_eq!(names(&index), vec!["tool_a", "tool_shared"]);
+
+        // 目录内容变化后 mtime 改变，需要重新扫描
+        create_executable(&dir_b.join("tool_b"));
+        index.dirs.get_mut(&dir_b).unwrap().mtime = None;
+        index.refresh_dirs(vec![dir_a.clone(), dir_b.clone()]);
+        assert_eq!(names(&index), vec!["tool_a", "tool_b", "tool_shared"]);
+
+        // 从 PATH 中移除的目录需要删除，其他目录中同名的命令保留
+        index.refresh_dirs(vec![dir_b.clone()]);
+        assert_eq!(names(&index), vec!["tool_b", "tool_shared"]);
+    }
+
+    #[test]
+    fn test_rank_commands() {
+        let commands = ["git", "gitk", "grep", "gzip", "echo", "exit"];
+        let frequency = HashMap::from([("gitk".to_string(), 3), ("grep".to_string(), 1)]);
+
+        // 前缀匹配优先，其次是拼写错误，最后是子序列匹配
+        assert_eq!(
+            rank_commands("gi", commands.into_iter(), &HashMap::new()),
+            vec!["git", "gitk", "gzip"]
+        );
+        assert_eq!(
+            rank_commands("gi", commands.into_iter(), &frequency)[..2],
+            ["gitk", "git"]
+        );
+        assert_eq!(
+            rank_commands("gti", commands.into_iter(), &HashMap::new()),
+            vec!["git", "gitk", "gzip"]
+        );
+        assert_eq!(
+            rank_commands("gp", commands.into_iter(), &frequency),
+            vec!["grep", "gzip"]
+        );
+        assert_eq!(
+            rank_commands("ext", commands.into_iter(), &frequency),
+            vec!["exit"]
+        );
+    }
+
+    #[test]
+    fn test_closest_commands() {
+        let commands = ["git", "gitk", "grep", "cat", "cargo", "echo"];
+        assert_eq!(closest_commands("gti", commands.into_iter()), vec!["git"]);
+        assert_eq!(
+            closest_commands("carg", commands.into_iter()),
+            vec!["cargo"]
+        );
+        assert_eq!(closest_commands("grpe", commands.into_iter()), vec!["grep"]);
+        assert!(closest_commands("ca", commands.into_iter()).is_empty());
+        assert!(closest_commands("invalid_command", commands.into_iter()).is_empty());
+    }
+}
@@ -1,86 +1,864 @@
-use std::{borrow::Cow, collections::HashSet, sync::RwLock};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::RwLock,
+    time::Duration,
+};
 
+use is_executable::IsExecutable;
 use lazy_static::lazy_static;
-use radix_trie::{Trie, TrieCommon};
-use rustyline::{Changeset, completion::Completer, line_buffer::LineBuffer};
+use regex::Regex;
+use rustyline::{
+    CompletionType,
+    completion::{Completer, Pair},
+    history::SearchDirection,
+};
 
 use crate::{
+    arithmetic::expand_arithmetic,
     builtin::BUILTIN_COMMANDS,
-    executable::{PATH_ENV, PATHS, load_env_path, load_paths},
-    utils::get_executables_from_dir,
+    command_index::{fuzzy_matches, prefix_matches, refresh_support_commands},
+    directory::expand_tilde,
+    executable::find_in_path,
+    help_completion::help_completion,
+    tokenize::tokenize,
+    utils::{read_command_output, user_names},
 };
 
 lazy_static! {
-    static ref SUPPORT_COMMANDS: RwLock<Trie<String, ()>> = {
-        let mut commands: HashSet<String> =
-            BUILTIN_COMMANDS.iter().map(|cmd| cmd.to_string()).collect();
-
-        for dir in PATHS.read().unwrap_or_else(|err| err.into_inner()).iter() {
-            for exec in get_executables_from_dir(dir) {
-                if let Some(exec) = exec.file_name().and_then(|basename| basename.to_str()) {
-                    commands.insert(exec.to_string());
+    static ref VARIABLE_RE: Regex = Regex::new(r"\$(\{?)([A-Za-z_][A-Za-z0-9_]*)?$").unwrap();
+    static ref VARIABLE_REF_RE: Regex =
+        Regex::new(r"\$(?:\{([A-Za-z_][A-Za-z0-9_]*)\}|([A-Za-z_][A-Za-z0-9_]*))").unwrap();
+    pub static ref COMPLETION_SPECS: RwLock<HashMap<String, CompletionSpec>> =
+        RwLock::new(HashMap::new());
+}
+
+const COMPLETION_COMMAND_TIMEOUT: Duration = Duration::from_secs(1);
+
+// 在引号外需要用 \ 转义的字符
+const ESCAPE_CHARS: &[char] = &[
+    ' ', '\t', '\n', '\'', '"', '\\', '$', '`', '&', '|', ';', '<', '>', '(', ')', '*', '?', '[',
+    ']', '{', '}', '!', '#', '~',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionAction {
+    File,
+    Directory,
+    Command,
+    Builtin,
+    Variable,
+}
+
+impl CompletionAction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let action = match name {
+            "file" => CompletionAction::File,
+            "directory" => CompletionAction::Directory,
+            "command" => CompletionAction::Command,
+            "builtin" => CompletionAction::Builtin,
+            "variable" => CompletionAction::Variable,
+            _ => return None,
+        };
+        Some(action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompletionAction::File => "file",
+            CompletionAction::Directory => "directory",
+            CompletionAction::Command => "command",
+            CompletionAction::Builtin => "builtin",
+            CompletionAction::Variable => "variable",
+        }
+    }
+}
+
+// 由 `complete` 注册的补全规则
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompletionSpec {
+    pub actions: Vec<CompletionAction>,
+    pub word_list: Option<String>,
+    // shell 不支持函数，-F 和 -C 一样，都是运行外部命令，并将输出的每一行作为候选项
+    pub function: Option<String>,
+    pub command: Option<String>,
+    pub nospace: bool,
+    pub default: bool,
+}
+
+impl Display for CompletionSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut options = vec![];
+        if self.nospace {
+            options.push("-o nospace".to_string());
+        }
+        if self.default {
+            options.push("-o default".to_string());
+        }
+        for action in &self.actions {
+            options.push(format!("-A {}", action.name()));
+        }
+        if let Some(word_list) = &self.word_list {
+            options.push(format!("-W '{}'", word_list.replace('\'', "'\\''")));
+        }
+        if let Some(function) = &self.function {
+            options.push(format!("-F {}", function));
+        }
+        if let Some(command) = &self.command {
+            options.push(format!("-C '{}'", command.replace('\'', "'\\''")));
+        }
+        write!(f, "{}", options.join(" "))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct CompletionContext {
+    // 光标所在单词在 line 中的起始位置
+    start: usize,
+    // 去掉引号和转义之后的单词
+    word: String,
+    // 光标处于未闭合的引号中
+    quote: Option<char>,
+    // 未闭合的引号在 line 中的位置
+    quote_start: Option<usize>,
+    // 当前命令中光标之前已经完整输入的单词，第一个是命令名
+    words: Vec<String>,
+    after_redirect: bool,
+}
+
+impl CompletionContext {
+    fn is_command(&self) -> bool {
+        self.words.is_empty() && !self.after_redirect
+    }
+
+    // 从 start 开始替换时如果会覆盖未闭合的左引号，候选项需要重新加上它
+    fn opening_quote(&self, start: usize) -> &'static str {
+        match (self.quote, self.quote_start) {
+            (Some('\''), Some(idx)) if idx >= start => "'",
+            (Some('"'), Some(idx)) if idx >= start => "\"",
+            _ => "",
+        }
+    }
+}
+
+fn parse_context(line: &str) -> CompletionContext {
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+    let mut word_start = None;
+    let mut quote = None;
+    let mut quote_start = None;
+    let mut after_redirect = false;
+
+    fn finish_word(
+        word: &mut String,
+        word_start: &mut Option<usize>,
+        words: &mut Vec<String>,
+        after_redirect: &mut bool,
+    ) {
+        if word_start.take().is_some() {
+            if *after_redirect {
+                // 重定向的目标文件不属于命令参数
+                *after_redirect = false;
+            } else {
+                words.push(word.clone());
+            }
+        }
+        word.clear();
+    }
+
+    let mut chars = line.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('"'), '\\') => match chars.next() {
+                Some((_, next @ ('"' | '\\' | '$' | '`'))) => word.push(next),
+                Some((_, next)) => {
+                    word.push('\\');
+                    word.push(next);
                 }
+                None => {}
+            },
+            (Some(_), _) => word.push(c),
+            (None, '\'' | '"') => {
+                word_start.get_or_insert(idx);
+                quote = Some(c);
+                quote_start = Some(idx);
             }
+            (None, '\\') => {
+                word_start.get_or_insert(idx);
+                if let Some((_, next)) = chars.next() {
+                    word.push(next);
+                }
+            }
+            (None, '|' | ';' | '&') => {
+                finish_word(&mut word, &mut word_start, &mut words, &mut after_redirect);
+                words.clear();
+                after_redirect = false;
+            }
+            (None, '<' | '>') => {
+                // 2> 中的 2 是重定向的一部分
+                if word.chars().all(|c| c.is_ascii_digit()) {
+                    word_start = None;
+                }
+                finish_word(&mut word, &mut word_start, &mut words, &mut after_redirect);
+                while let Some((_, '>' | '&')) = chars.peek() {
+                    chars.next();
+                }
+                after_redirect = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                finish_word(&mut word, &mut word_start, &mut words, &mut after_redirect);
+            }
+            (None, _) => {
+                word_start.get_or_insert(idx);
+                word.push(c);
+            }
+        }
+    }
+
+    CompletionContext {
+        start: word_start.unwrap_or(line.len()),
+        word,
+        quote,
+        quote_start: quote.and(quote_start),
+        words,
+        after_redirect,
+    }
+}
+
+fn escape(s: &str, quote: Option<char>) -> String {
+    match quote {
+        Some('\'') => s.replace('\'', "'\\''"),
+        Some(_) => s.chars().fold(String::new(), |mut acc, c| {
+            if matches!(c, '"' | '\\' | '$' | '`') {
+                acc.push('\\');
+            }
+            acc.push(c);
+            acc
+        }),
+        None => s.chars().fold(String::new(), |mut acc, c| {
+            if ESCAPE_CHARS.contains(&c) {
+                acc.push('\\');
+            }
+            acc.push(c);
+            acc
+        }),
+    }
+}
+
+// 只有唯一候选项时才补全结尾的引号和空格，方便继续输入下一个参数
+fn finish_single_candidate(candidates: &mut [Pair], quote: Option<char>) {
+    if let [candidate] = candidates
+        && !candidate.replacement.ends_with('/')
+    {
+        if let Some(quote) = quote {
+            candidate.replacement.push(quote);
         }
+        candidate.replacement.push(' ');
+    }
+}
+
+// COMPLETION_MODE=fuzzy 时命令名按模糊匹配得分和历史记录中的使用次数排序，
+// 并通过 Tab 依次切换候选项；fuzzy-select 则使用 rustyline 的交互式选择界面
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionMode {
+    Prefix,
+    Fuzzy,
+    FuzzySelect,
+}
+
+impl CompletionMode {
+    pub fn current() -> Self {
+        match env::var("COMPLETION_MODE").as_deref() {
+            Ok("fuzzy") => CompletionMode::Fuzzy,
+            Ok("fuzzy-select") => CompletionMode::FuzzySelect,
+            _ => CompletionMode::Prefix,
+        }
+    }
 
-        RwLock::new(Trie::from_iter(commands.into_iter().map(|cmd| (cmd, ()))))
+    pub fn completion_type(&self) -> CompletionType {
+        match self {
+            CompletionMode::Prefix => CompletionType::List,
+            CompletionMode::Fuzzy => CompletionType::Circular,
+            CompletionMode::FuzzySelect => CompletionType::Fuzzy,
+        }
+    }
+}
+
+fn command_frequency(history: &dyn rustyline::history::History) -> HashMap<String, usize> {
+    let mut frequency = HashMap::new();
+    for idx in 0..history.len() {
+        if let Ok(Some(entry)) = history.get(idx, SearchDirection::Forward)
+            && let Some(cmd) = entry.entry.split_whitespace().next()
+        {
+            *frequency.entry(cmd.to_string()).or_insert(0) += 1;
+        }
+    }
+    frequency
+}
+
+fn complete_command(
+    context: &CompletionContext,
+    history: Option<&dyn rustyline::history::History>,
+) -> Vec<Pair> {
+    let commands = match (CompletionMode::current(), history) {
+        (CompletionMode::Prefix, _) | (_, None) => prefix_matches(&context.word),
+        (_, Some(history)) => fuzzy_matches(&context.word, &command_frequency(history)),
     };
+    commands
+        .into_iter()
+        .map(|cmd| Pair {
+            replacement: context.opening_quote(context.start).to_string()
+                + &escape(&cmd, context.quote),
+            display: cmd,
+        })
+        .collect()
+}
+
+// 只用于查找目录，不会修改 line 中用户输入的内容
+fn expand_dir(dir: &str) -> PathBuf {
+    let dir = VARIABLE_REF_RE.replace_all(dir, |caps: &regex::Captures| {
+        let name = caps.get(1).or(caps.get(2)).unwrap().as_str();
+        env::var(name).unwrap_or_default()
+    });
+    if dir.is_empty() {
+        return PathBuf::from(".");
+    }
+    if let Some(expanded) = expand_tilde(&dir) {
+        return PathBuf::from(expanded);
+    }
+    PathBuf::from(dir.as_ref())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PathFilter {
+    All,
+    Directories,
+    Executables,
+}
+
+fn complete_path(
+    line: &str,
+    pos: usize,
+    context: &CompletionContext,
+    filter: PathFilter,
+) -> (usize, Vec<Pair>) {
+    complete_path_in(line, pos, context, filter, Path::new(""))
+}
+
+// cd 需要在 CDPATH 中的目录下查找
+fn complete_cd_path(line: &str, pos: usize, context: &CompletionContext) -> (usize, Vec<Pair>) {
+    complete_cd_path_in(line, pos, context, &env::var("CDPATH").unwrap_or_default())
+}
+
+fn complete_cd_path_in(
+    line: &str,
+    pos: usize,
+    context: &CompletionContext,
+    cdpath: &str,
+) -> (usize, Vec<Pair>) {
+    let (start, mut candidates) = complete_path(line, pos, context, PathFilter::Directories);
+    if !context.word.starts_with(['/', '.', '~', '$']) {
+        for base in cdpath.split(':') {
+            if !base.is_empty() {
+                candidates.extend(
+                    complete_path_in(line, pos, context, PathFilter::Directories, Path::new(base))
+                        .1,
+                );
+            }
+        }
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates.dedup_by(|a, b| a.replacement == b.replacement);
+    }
+    (start, candidates)
+}
+
+fn complete_path_in(
+    line: &str,
+    pos: usize,
+    context: &CompletionContext,
+    filter: PathFilter,
+    base: &Path,
+) -> (usize, Vec<Pair>) {
+    // 只替换最后一个 / 之后的部分，用户输入的目录部分保持原样
+    let raw_word = &line[context.start..pos];
+    let start = raw_word
+        .rfind('/')
+        .map_or(context.start, |idx| context.start + idx + 1);
+    let (dir, prefix) = match context.word.rfind('/') {
+        Some(idx) => context.word.split_at(idx + 1),
+        None => ("", context.word.as_str()),
+    };
+    let mut candidates = vec![];
+    if let Ok(entries) = fs::read_dir(base.join(expand_dir(dir))) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                continue;
+            }
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            let matched = match filter {
+                PathFilter::All => true,
+                PathFilter::Directories => is_dir,
+                PathFilter::Executables => is_dir || path.is_executable(),
+            };
+            if !matched {
+                continue;
+            }
+            let suffix = if is_dir { "/" } else { "" };
+            candidates.push(Pair {
+                display: name.clone() + suffix,
+                replacement: context.opening_quote(start).to_string()
+                    + &escape(&name, context.quote)
+                    + suffix,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| a.display.cmp(&b.display));
+    (start, candidates)
+}
+
+// $VAR 和 ${VAR} 的补全
+fn complete_variable(
+    line: &str,
+    pos: usize,
+    context: &CompletionContext,
+) -> Option<(usize, Vec<Pair>)> {
+    if context.quote == Some('\'') {
+        return None;
+    }
+    let caps = VARIABLE_RE.captures(&line[context.start..pos])?;
+    let braced = !caps[1].is_empty();
+    let prefix = caps.get(2).map_or("", |m| m.as_str());
+    let start = context.start + caps.get(0).unwrap().start() + 1 + braced as usize;
+
+    let mut names: Vec<String> = env::vars()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    let candidates = names
+        .into_iter()
+        .map(|name| Pair {
+            replacement: if braced {
+                format!("{}}}", name)
+            } else {
+                name.clone()
+            },
+            display: name,
+        })
+        .collect();
+    Some((start, candidates))
+}
+
+// ~user 的补全
+fn complete_user(context: &CompletionContext) -> Option<Vec<Pair>> {
+    let prefix = context.word.strip_prefix('~')?;
+    if context.quote.is_some() || prefix.contains('/') {
+        return None;
+    }
+    let mut names: Vec<String> = user_names()
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    names.sort();
+    names.dedup();
+    Some(
+        names
+            .into_iter()
+            .map(|name| Pair {
+                display: format!("~{}", name),
+                replacement: format!("~{}/", escape(&name, None)),
+            })
+            .collect(),
+    )
+}
+
+fn filter_words<'a>(
+    words: impl Iterator<Item = &'a str>,
+    context: &CompletionContext,
+) -> Vec<Pair> {
+    words
+        .filter(|word| word.starts_with(&context.word))
+        .map(|word| Pair {
+            display: word.to_string(),
+            replacement: context.opening_quote(context.start).to_string()
+                + &escape(word, context.quote),
+        })
+        .collect()
+}
+
+fn run_completion_command(
+    command: &str,
+    line: &str,
+    pos: usize,
+    context: &CompletionContext,
+) -> Vec<String> {
+    let argv: Vec<String> = tokenize(command)
+        .and_then(|argv| argv.iter().map(|arg| expand_arithmetic(arg)).collect())
+        .unwrap_or_default();
+    let Some(exec_path) = argv.first().and_then(|exec| find_in_path(exec)) else {
+        return vec![];
+    };
+    // 与 bash 一致: $1 为命令名，$2 为待补全的单词，$3 为前一个单词
+    let output = read_command_output(
+        process::Command::new(exec_path)
+            .args(&argv[1..])
+            .arg(&context.words[0])
+            .arg(&context.word)
+            .arg(context.words.last().unwrap())
+            .env("COMP_LINE", line)
+            .env("COMP_POINT", pos.to_string())
+            .env("COMP_CWORD", context.words.len().to_string()),
+        COMPLETION_COMMAND_TIMEOUT,
+    );
+    output
+        .map(|output| output.lines().map(|line| line.to_string()).collect())
+        .unwrap_or_default()
+}
+
+// 没有注册补全规则时，解析 `cmd --help` 的输出补全 flag 和子命令
+fn complete_from_help(context: &CompletionContext) -> Option<Vec<Pair>> {
+    let command = context.words.first()?;
+    if context.after_redirect
+        || context.word.contains('/')
+        || BUILTIN_COMMANDS.contains(command.as_str())
+    {
+        return None;
+    }
+    let help = help_completion(&find_in_path(command)?)?;
+    let candidates = if context.word.starts_with('-') {
+        filter_words(help.flags.iter().map(|s| s.as_str()), context)
+    } else if context.words.len() == 1 {
+        filter_words(help.subcommands.iter().map(|s| s.as_str()), context)
+    } else {
+        return None;
+    };
+    (!candidates.is_empty()).then_some(candidates)
+}
+
+fn complete_with_spec(
+    line: &str,
+    pos: usize,
+    context: &CompletionContext,
+    spec: &CompletionSpec,
+) -> Vec<Pair> {
+    let mut candidates = vec![];
+    for action in &spec.actions {
+        match action {
+            CompletionAction::File | CompletionAction::Directory => {
+                let filter = if *action == CompletionAction::File {
+                    PathFilter::All
+                } else {
+                    PathFilter::Directories
+                };
+                // 路径补全只替换最后一个 / 之后的部分，这里需要和其他候选项统一起始位置
+                let (start, paths) = complete_path(line, pos, context, filter);
+                let dir = &line[context.start..start];
+                candidates.extend(paths.into_iter().map(|path| Pair {
+                    display: path.display,
+                    replacement: dir.to_string() + &path.replacement,
+                }));
+            }
+            CompletionAction::Command => candidates.extend(complete_command(context, None)),
+            CompletionAction::Builtin => {
+                candidates.extend(filter_words(BUILTIN_COMMANDS.iter().copied(), context))
+            }
+            CompletionAction::Variable => {
+                let names: Vec<String> = env::vars().map(|(name, _)| name).collect();
+                candidates.extend(filter_words(names.iter().map(|s| s.as_str()), context));
+            }
+        }
+    }
+    if let Some(word_list) = &spec.word_list {
+        candidates.extend(filter_words(word_list.split_whitespace(), context));
+    }
+    for command in spec.function.iter().chain(spec.command.iter()) {
+        let words = run_completion_command(command, line, pos, context);
+        candidates.extend(filter_words(words.iter().map(|s| s.as_str()), context));
+    }
+
+    candidates.sort_by(|a, b| a.display.cmp(&b.display));
+    candidates.dedup_by(|a, b| a.replacement == b.replacement);
+    if candidates.is_empty() && spec.default {
+        let (start, paths) = complete_path(line, pos, context, PathFilter::All);
+        let dir = &line[context.start..start];
+        candidates.extend(paths.into_iter().map(|path| Pair {
+            display: path.display,
+            replacement: dir.to_string() + &path.replacement,
+        }));
+    }
+    candidates
 }
 
 pub struct ShellCompleter;
 
 impl Completer for ShellCompleter {
-    type Candidate = String;
+    type Candidate = Pair;
 
     fn complete(
         &self, // FIXME should be `&mut self`
         line: &str,
         pos: usize,
-        _ctx: &rustyline::Context<'_>,
+        ctx: &rustyline::Context<'_>,
     ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
-        // 检查 PATH 是否有更新
-        let new_env_path = load_env_path();
-        let old_env_path = PATH_ENV.read().unwrap_or_else(|err| err.into_inner());
-        if new_env_path.as_str() != old_env_path.as_str() {
-            *PATH_ENV.write().unwrap() = new_env_path;
-
-            let new_paths = HashSet::from_iter(load_paths());
-            let old_paths = PATHS.read().unwrap_or_else(|err| err.into_inner());
-            let added_paths = new_paths.difference(&old_paths);
-
-            let mut writer = SUPPORT_COMMANDS.write().unwrap();
-            for dir in added_paths {
-                for exec in get_executables_from_dir(dir) {
-                    if let Some(exec) = exec.file_name().and_then(|basename| basename.to_str()) {
-                        writer.insert(exec.to_string(), ());
-                    }
-                }
+        refresh_support_commands();
+
+        let context = parse_context(&line[..pos]);
+        // 以重定向开头的行，比如 `> fo`，没有命令名，只补全文件
+        let spec = match context.words.first() {
+            Some(command) if !context.is_command() => {
+                COMPLETION_SPECS.read().unwrap().get(command).cloned()
             }
+            _ => None,
+        };
+        let (start, mut candidates) =
+            if let Some(completion) = complete_variable(line, pos, &context) {
+                completion
+            } else if let Some(candidates) = complete_user(&context) {
+                (context.start, candidates)
+            } else if let Some(spec) = &spec {
+                (context.start, complete_with_spec(line, pos, &context, spec))
+            } else if context.is_command() && !context.word.contains('/') {
+                (
+                    context.start,
+                    complete_command(&context, Some(ctx.history())),
+                )
+            } else if context.is_command() {
+                complete_path(line, pos, &context, PathFilter::Executables)
+            } else if context.words.first().is_some_and(|command| command == "cd")
+                && !context.after_redirect
+            {
+                complete_cd_path(line, pos, &context)
+            } else if let Some(candidates) = complete_from_help(&context) {
+                (context.start, candidates)
+            } else {
+                complete_path(line, pos, &context, PathFilter::All)
+            };
+        if !spec.is_some_and(|spec| spec.nospace) {
+            finish_single_candidate(&mut candidates, context.quote);
         }
+        Ok((start, candidates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_context() {
+        let context = parse_context("ech");
+        assert_eq!(context.start, 0);
+        assert_eq!(context.word, "ech");
+        assert!(context.is_command());
+
+        let context = parse_context("cat sr");
+        assert_eq!(context.start, 4);
+        assert_eq!(context.word, "sr");
+        assert_eq!(context.words, vec_str_to_vec_string::<Vec<_>>(&["cat"]));
+        assert!(!context.is_command());
+
+        let context = parse_context("cat 'my fi");
+        assert_eq!(context.start, 4);
+        assert_eq!(context.word, "my fi");
+        assert_eq!(context.quote, Some('\''));
+
+        let context = parse_context("ls my\\ di");
+        assert_eq!(context.start, 3);
+        assert_eq!(context.word, "my di");
+        assert_eq!(context.quote, None);
 
-        //TODO 支持 command, args 区分，支持不同类型的补全
-        if let Some(sub_trie) = SUPPORT_COMMANDS.read().unwrap().get_raw_descendant(line) {
-            // dbg!(&sub_trie);
+        let context = parse_context("ls src | gr");
+        assert_eq!(context.word, "gr");
+        assert!(context.is_command());
+        let context = parse_context("cd /tmp && ");
+        assert_eq!(context.start, 11);
+        assert!(context.is_command());
 
-            // candidates 无需排序，trie 中取出来之后就是按字典序排好序的
-            let candidates: Vec<String> = sub_trie.keys().map(|key| key.to_string()).collect();
-            // dbg!(&candidates);
+        let context = parse_context("echo hello 2> ou");
+        assert_eq!(context.word, "ou");
+        assert_eq!(
+            context.words,
+            vec_str_to_vec_string::<Vec<_>>(&["echo", "hello"])
+        );
+        assert!(context.after_redirect);
+    }
+
+    #[test]
+    fn test_complete_quoted() {
+        let dir = env::temp_dir().join(format!("completer-quoted-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("my file"), "").unwrap();
+        let complete = |line: &str| {
+            let context = parse_context(line);
+            let (start, mut candidates) =
+                complete_path(line, line.len(), &context, PathFilter::All);
+            finish_single_candidate(&mut candidates, context.quote);
+            let mut completed = line[..start].to_string();
+            completed.push_str(&candidates[0].replacement);
+            completed
+        };
+        let dir_str = dir.display().to_string();
+        assert_eq!(
+            complete(&format!("cat '{}/my fi", dir_str)),
+            format!("cat '{}/my file' ", dir_str)
+        );
+        assert_eq!(
+            complete(&format!("cat {}/\"my fi", dir_str)),
+            format!("cat {}/\"my file\" ", dir_str)
+        );
+        fs::remove_dir_all(&dir).ok();
+
+        let context = parse_context("'ech");
+        let candidates = complete_command(&context, None);
+        assert!(
+            candidates
+                .iter()
+                .any(|candidate| candidate.replacement == "'echo")
+        );
+        let context = parse_context("ls; e'ch");
+        let candidates = complete_command(&context, None);
+        assert!(
+            candidates
+                .iter()
+                .any(|candidate| candidate.replacement == "'echo")
+        );
+    }
 
-            Ok((0, candidates))
-        } else {
-            Ok((pos, Vec::with_capacity(0)))
+    #[test]
+    fn test_complete_redirect_without_command() {
+        let dir = env::temp_dir().join(format!("completer-redirect-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("foo.txt"), "").unwrap();
+        let line = format!("> {}/fo", dir.display());
+        let history = rustyline::history::MemHistory::new();
+        let result = ShellCompleter.complete(&line, line.len(), &rustyline::Context::new(&history));
+        fs::remove_dir_all(&dir).ok();
+
+        let (start, candidates) = result.unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(
+            format!("{}{}", &line[..start], candidates[0].replacement),
+            format!("> {}/foo.txt ", dir.display())
+        );
+    }
+
+    #[test]
+    fn test_complete_variable() {
+        unsafe {
+            env::set_var("COMPLETER_TEST_ALPHA", "1");
+            env::set_var("COMPLETER_TEST_BETA", "2");
         }
+        let complete = |line: &str| {
+            complete_variable(line, line.len(), &parse_context(line)).map(|(start, candidates)| {
+                let replacements: Vec<String> = candidates
+                    .into_iter()
+                    .map(|candidate| candidate.replacement)
+                    .collect();
+                (start, replacements)
+            })
+        };
+        assert_eq!(
+            complete("echo $COMPLETER_TEST_"),
+            Some((
+                6,
+                vec_str_to_vec_string(&["COMPLETER_TEST_ALPHA", "COMPLETER_TEST_BETA"])
+            ))
+        );
+        assert_eq!(
+            complete("echo a${COMPLETER_TEST_B"),
+            Some((8, vec_str_to_vec_string(&["COMPLETER_TEST_BETA}"])))
+        );
+        assert_eq!(
+            complete("echo \"$COMPLETER_TEST_A"),
+            Some((7, vec_str_to_vec_string(&["COMPLETER_TEST_ALPHA"])))
+        );
+        assert_eq!(complete("echo '$COMPLETER_TEST_"), None);
+        assert_eq!(complete("echo COMPLETER_TEST_"), None);
     }
 
-    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str, cl: &mut Changeset) {
-        let end = line.pos();
-        let elected = if let Some(sub_trie) = SUPPORT_COMMANDS.read().unwrap().subtrie(elected)
-            && sub_trie.is_leaf()
-        {
-            Cow::Owned(elected.to_string() + " ")
-        } else {
-            Cow::Borrowed(elected)
+    #[test]
+    fn test_complete_variable_dir() {
+        let dir = env::temp_dir().join(format!("completer-variable-{}", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        unsafe { env::set_var("COMPLETER_TEST_DIR", &dir) };
+        let line = "ls ${COMPLETER_TEST_DIR}/s";
+        let (start, candidates) =
+            complete_path(line, line.len(), &parse_context(line), PathFilter::All);
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(start, line.len() - 1);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].replacement, "sub/");
+    }
+
+    #[test]
+    fn test_complete_user() {
+        let complete = |line: &str| {
+            complete_user(&parse_context(line)).map(|candidates| {
+                candidates
+                    .into_iter()
+                    .map(|candidate| candidate.replacement)
+                    .collect::<Vec<_>>()
+            })
         };
-        line.replace(start..end, &elected, cl);
+        assert!(complete("ls ~roo").unwrap().contains(&"~root/".to_string()));
+        assert!(
+            complete("ls ~")
+                .unwrap()
+                .iter()
+                .all(|name| name.starts_with('~') && name.ends_with('/'))
+        );
+        assert_eq!(complete("ls ~no_such_user_123"), Some(vec![]));
+        assert_eq!(complete("ls ~root/"), None);
+        assert_eq!(complete("ls '~roo"), None);
+        assert_eq!(complete("ls roo"), None);
+    }
+
+    #[test]
+    fn test_complete_cd_path() {
+        let root = env::temp_dir().join(format!("completer-cdpath-{}", process::id()));
+        fs::create_dir_all(root.join("first/project")).unwrap();
+        fs::create_dir_all(root.join("second/proto")).unwrap();
+        fs::write(root.join("second/profile"), "").unwrap();
+        let cdpath = format!(
+            "{}::{}",
+            root.join("first").display(),
+            root.join("second").display()
+        );
+        let complete = |line: &str| {
+            let (_, candidates) =
+                complete_cd_path_in(line, line.len(), &parse_context(line), &cdpath);
+            candidates
+                .into_iter()
+                .map(|candidate| candidate.replacement)
+                .collect::<Vec<_>>()
+        };
+        let found = complete("cd pro");
+        let absolute = complete(&format!("cd {}/pro", root.join("second").display()));
+        let dotted = complete("cd ./pro");
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(
+            found,
+            vec_str_to_vec_string::<Vec<_>>(&["project/", "proto/"])
+        );
+        assert_eq!(absolute, vec_str_to_vec_string::<Vec<_>>(&["proto/"]));
+        assert!(dotted.is_empty());
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("file name", None), "file\\ name");
+        assert_eq!(escape("it's", Some('\'')), "it'\\''s");
+        assert_eq!(escape("a\"$b", Some('"')), "a\\\"\\$b");
     }
 }
@@ -0,0 +1,195 @@
+use std::{
+    env, fs, io,
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+
+use crate::utils::{is_main_thread, user_home_dir};
+
+lazy_static! {
+    // pushd 保存的目录，不包含当前目录 (dirs 中的第 0 项)
+    pub static ref DIR_STACK: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+}
+
+// 逻辑路径，保留 cd 经过的符号链接；PWD 失效时退回到物理路径
+pub fn logical_cwd() -> PathBuf {
+    let cwd = env::current_dir().unwrap_or_default();
+    if let Ok(pwd) = env::var("PWD") {
+        let pwd = PathBuf::from(pwd);
+        if pwd.is_absolute() && fs::canonicalize(&pwd).ok() == fs::canonicalize(&cwd).ok() {
+            return pwd;
+        }
+    }
+    cwd
+}
+
+pub fn physical_cwd() -> io::Result<PathBuf> {
+    env::current_dir().and_then(fs::canonicalize)
+}
+
+// 只处理 . 和 ..，不访问文件系统
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// 切换目录并维护 PWD 和 OLDPWD，返回新的 PWD
+pub fn change_dir(target: &Path, physical: bool) -> io::Result<PathBuf> {
+    let old_pwd = logical_cwd();
+    let new_pwd = if physical {
+        fs::canonicalize(target)?
+    } else {
+        normalize_path(&old_pwd.join(target))
+    };
+    // 管道中前面的命令在单独的线程中运行，相当于 bash 的子 shell，其中的 cd 只检查目标目录，
+    // 不改变当前 shell 的目录，也避免在其他线程读取环境变量时修改它
+    if !is_main_thread() {
+        let target = if physical { &new_pwd } else { target };
+        return match fs::metadata(old_pwd.join(target)) {
+            Ok(meta) if meta.is_dir() => Ok(new_pwd),
+            Ok(_) => Err(io::ErrorKind::NotADirectory.into()),
+            Err(err) => Err(err),
+        };
+    }
+    // 逻辑路径中的 .. 可能无法访问 (比如上级目录没有权限)，此时按照物理路径处理
+    let new_pwd = match env::set_current_dir(&new_pwd) {
+        Ok(()) => new_pwd,
+        Err(_) if !physical => {
+            env::set_current_dir(target)?;
+            physical_cwd()?
+        }
+        Err(err) => return Err(err),
+    };
+    unsafe {
+        env::set_var("OLDPWD", &old_pwd);
+        env::set_var("PWD", &new_pwd);
+    }
+    Ok(new_pwd)
+}
+
+pub fn describe_dir_error(err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::NotFound => "No such file or directory".to_string(),
+        io::ErrorKind::NotADirectory => "Not a directory".to_string(),
+        io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        _ => err.to_string(),
+    }
+}
+
+// dirs 中显示的完整目录列表，第 0 项为当前目录
+pub fn dir_stack_entries() -> Vec<PathBuf> {
+    let stack = DIR_STACK.lock().unwrap_or_else(|err| err.into_inner());
+    [logical_cwd()]
+        .into_iter()
+        .chain(stack.iter().cloned())
+        .collect()
+}
+
+// ~N 和 ~+N 从左往右数，~-N 从右往左数
+pub fn dir_stack_index(spec: &str, len: usize) -> Option<usize> {
+    let (from_end, num) = match spec.strip_prefix('-') {
+        Some(num) => (true, num),
+        None => (false, spec.strip_prefix('+').unwrap_or(spec)),
+    };
+    if num.is_empty() || !num.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let num: usize = num.parse().ok()?;
+    if num >= len {
+        None
+    } else if from_end {
+        Some(len - 1 - num)
+    } else {
+        Some(num)
+    }
+}
+
+pub fn abbreviate_home(path: &Path) -> String {
+    match env::home_dir().and_then(|home| path.strip_prefix(home).ok().map(|p| p.to_path_buf())) {
+        Some(relative) if relative.as_os_str().is_empty() => "~".to_string(),
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
+
+// 展开单词开头的 ~，~+，~-，~N 和 ~user，无法展开时返回 None
+pub fn expand_tilde(word: &str) -> Option<String> {
+    let rest = word.strip_prefix('~')?;
+    let (prefix, rest) = match rest.find('/') {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, ""),
+    };
+    let dir = match prefix {
+        "" => env::var("HOME")
+            .ok()
+            .map(PathBuf::from)
+            .or_else(env::home_dir)?,
+        "+" => logical_cwd(),
+        "-" => PathBuf::from(env::var("OLDPWD").ok()?),
+        _ if prefix.starts_with(|c: char| c.is_ascii_digit() || c == '+' || c == '-') => {
+            let entries = dir_stack_entries();
+            entries[dir_stack_index(prefix, entries.len())?].clone()
+        }
+        user => user_home_dir(user)?,
+    };
+    Some(format!("{}{}", dir.display(), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("/usr/local/../bin/./ls")),
+            PathBuf::from("/usr/bin/ls")
+        );
+        assert_eq!(normalize_path(Path::new("/..")), PathBuf::from("/"));
+    }
+
+    #[test]
+    fn test_dir_stack_index() {
+        assert_eq!(dir_stack_index("0", 3), Some(0));
+        assert_eq!(dir_stack_index("+2", 3), Some(2));
+        assert_eq!(dir_stack_index("-0", 3), Some(2));
+        assert_eq!(dir_stack_index("-2", 3), Some(0));
+        assert_eq!(dir_stack_index("3", 3), None);
+        assert_eq!(dir_stack_index("+", 3), None);
+        assert_eq!(dir_stack_index("1a", 3), None);
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = env::var("HOME").unwrap();
+        assert_eq!(expand_tilde("~"), Some(home.clone()));
+        assert_eq!(expand_tilde("~/src"), Some(format!("{}/src", home)));
+        assert_eq!(
+            expand_tilde("~+"),
+            Some(logical_cwd().display().to_string())
+        );
+        assert_eq!(
+            expand_tilde("~0/a"),
+            Some(format!("{}/a", logical_cwd().display()))
+        );
+        let root_home = fs::read_to_string("/etc/passwd")
+            .unwrap()
+            .lines()
+            .find_map(|line| line.strip_prefix("root:")?.split(':').nth(4))
+            .map(str::to_string);
+        assert_eq!(expand_tilde("~root"), root_home);
+        assert_eq!(expand_tilde("~no_such_user_123"), None);
+        assert_eq!(expand_tilde("a~"), None);
+    }
+}
@@ -1,4 +1,12 @@
-use std::{collections::HashSet, env, path::PathBuf, process, sync::RwLock};
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    io::{self, Read, Write},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+};
 
 use is_executable::IsExecutable;
 use lazy_static::lazy_static;
@@ -10,31 +18,121 @@ use crate::{
 };
 
 lazy_static! {
-    pub static ref PATH_ENV: RwLock<String> = RwLock::new(load_env_path());
-    pub static ref PATHS: RwLock<HashSet<PathBuf>> = RwLock::new(HashSet::from_iter(load_paths()));
+    pub static ref HASHED_COMMANDS: Mutex<HashTable> = Mutex::new(HashTable::default());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashedCommand {
+    pub path: PathBuf,
+    pub hits: usize,
+}
+
+// 与 bash 的 hash 表一致，记住命令对应的路径，避免每次都遍历 PATH
+#[derive(Debug, Default)]
+pub struct HashTable {
+    path_env: String,
+    entries: BTreeMap<String, HashedCommand>,
+}
+
+impl HashTable {
+    // PATH 被修改后，之前记住的路径全部失效
+    fn sync_path_env(&mut self) {
+        let path_env = load_env_path();
+        if path_env != self.path_env {
+            self.entries.clear();
+            self.path_env = path_env;
+        }
+    }
+
+    pub fn get(&mut self, name: &str) -> Option<&HashedCommand> {
+        self.sync_path_env();
+        self.entries.get(name)
+    }
+
+    pub fn entries(&mut self) -> &BTreeMap<String, HashedCommand> {
+        self.sync_path_env();
+        &self.entries
+    }
+
+    pub fn lookup(&mut self, name: &str) -> Option<PathBuf> {
+        self.sync_path_env();
+        if let Some(hashed) = self.entries.get_mut(name)
+            && hashed.path.is_executable()
+        {
+            hashed.hits += 1;
+            return Some(hashed.path.clone());
+        }
+
+        let path = find_in_path(name)?;
+        self.entries.insert(
+            name.to_string(),
+            HashedCommand {
+                path: path.clone(),
+                hits: 1,
+            },
+        );
+        Some(path)
+    }
+
+    pub fn remember(&mut self, name: &str, path: PathBuf) {
+        self.sync_path_env();
+        self.entries
+            .insert(name.to_string(), HashedCommand { path, hits: 0 });
+    }
+
+    pub fn forget(&mut self, name: &str) -> bool {
+        self.sync_path_env();
+        self.entries.remove(name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+// 带有 / 的命令不查找 PATH，也不记录到 hash 表中
+pub fn lookup_command(command: &str) -> Option<PathBuf> {
+    if command.contains('/') {
+        find_in_path(command)
+    } else {
+        HASHED_COMMANDS
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .lookup(command)
+    }
 }
 
 pub fn load_env_path() -> String {
     env::var("PATH").expect("Invalid $PATH")
 }
 
-#[allow(unused)]
 pub fn load_paths() -> Vec<PathBuf> {
-    //TODO 是否可以用 HashSet，还是应该用 Vec?
     env::split_paths(&load_env_path())
         .filter(|path| path.is_dir())
         .collect()
 }
 
+fn is_executable_file(path: &Path) -> bool {
+    path.is_file() && path.is_executable()
+}
+
+// 带有 / 的命令直接按路径查找，否则只在 PATH 中查找，不查找当前目录。
+// 返回的路径总是包含 /，这样 spawn 时不会再次查找 PATH
 pub fn find_in_path(executable: &str) -> Option<PathBuf> {
-    let executable = PathBuf::from(executable);
-    if executable.exists() && executable.is_executable() {
-        return Some(executable);
+    if executable.contains('/') {
+        let executable = PathBuf::from(executable);
+        return is_executable_file(&executable).then_some(executable);
     }
 
     for dir in env::split_paths(&load_env_path()) {
-        let candidate = dir.join(&executable);
-        if candidate.exists() && candidate.is_executable() {
+        // PATH 中的空项表示当前目录
+        let dir = if dir.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            dir
+        };
+        let candidate = dir.join(executable);
+        if is_executable_file(&candidate) {
             return Some(candidate);
         }
     }
@@ -59,34 +157,113 @@ impl Parse for Executable {
     where
         Self: std::marker::Sized,
     {
-        if let Some(exec_path) = find_in_path(command) {
+        if let Some(exec_path) = lookup_command(command) {
             Ok(Executable::new(
                 command.to_string(),
                 exec_path,
                 args.to_vec(),
             ))
+        } else if command.contains('/') && PathBuf::from(command).exists() {
+            // 文件存在但无法执行，交给 spawn 报告具体的错误
+            Ok(Executable::new(
+                command.to_string(),
+                PathBuf::from(command),
+                args.to_vec(),
+            ))
         } else {
             Err("Cannot find executable".into())
         }
     }
 }
 
+impl Executable {
+    // 与 bash 一致: 找不到文件返回 127，文件存在但无法执行返回 126
+    fn spawn_error(&self, err: &io::Error) -> (String, ExitCode) {
+        if self.path.is_dir() {
+            return (format!("{}: Is a directory", self.name), 126);
+        }
+        match err.kind() {
+            io::ErrorKind::NotFound if self.path.exists() => {
+                let interpreter = read_shebang(&self.path).unwrap_or_default();
+                (
+                    format!(
+                        "{}: {}: bad interpreter: No such file or directory",
+                        self.name, interpreter
+                    ),
+                    126,
+                )
+            }
+            io::ErrorKind::NotFound => (format!("{}: No such file or directory", self.name), 127),
+            io::ErrorKind::PermissionDenied => (format!("{}: Permission denied", self.name), 126),
+            _ if err.raw_os_error() == Some(libc::ENOEXEC) => {
+                if is_binary_file(&self.path) {
+                    (
+                        format!(
+                            "{}: cannot execute binary file: Exec format error",
+                            self.name
+                        ),
+                        126,
+                    )
+                } else {
+                    (
+                        format!("{}: cannot execute: missing #! interpreter line", self.name),
+                        126,
+                    )
+                }
+            }
+            _ => (format!("{}: {}", self.name, err), 126),
+        }
+    }
+}
+
+// 内核只读取 #! 行的前 256 个字节
+fn read_shebang(path: &PathBuf) -> Option<String> {
+    let mut buf = [0; 256];
+    let len = fs::File::open(path)
+        .and_then(|mut file| file.read(&mut buf))
+        .ok()?;
+    let first_line = buf[..len].split(|byte| *byte == b'\n').next()?;
+    let interpreter = String::from_utf8_lossy(first_line.strip_prefix(b"#!")?);
+    interpreter
+        .split_whitespace()
+        .next()
+        .map(|interpreter| interpreter.to_string())
+}
+
+fn is_binary_file(path: &PathBuf) -> bool {
+    let mut buf = [0; 512];
+    fs::File::open(path)
+        .and_then(|mut file| file.read(&mut buf))
+        .is_ok_and(|len| buf[..len].contains(&0))
+}
+
 impl Execute for Executable {
-    fn execute(&self, reader: Reader, output_writer: Writer, error_writer: Writer) -> ExitCode {
-        if let Ok(mut child) = process::Command::new(&self.name)
+    fn execute(&self, reader: Reader, output_writer: Writer, mut error_writer: Writer) -> ExitCode {
+        // 使用解析好的路径运行，argv[0] 保持用户输入的命令名
+        let spawn_res = process::Command::new(&self.path)
+            .arg0(&self.name)
             .args(&self.args)
             .stdin(reader)
             .stdout(output_writer)
-            .stderr(error_writer)
-            .spawn()
-        {
-            if let Ok(exit_status) = child.wait() {
-                exit_status.code().unwrap_or(-1)
-            } else {
-                -1
+            .stderr(
+                error_writer
+                    .try_clone()
+                    .map_or(process::Stdio::inherit(), process::Stdio::from),
+            )
+            .spawn();
+        match spawn_res {
+            Ok(mut child) => {
+                if let Ok(exit_status) = child.wait() {
+                    exit_status.code().unwrap_or(-1)
+                } else {
+                    -1
+                }
+            }
+            Err(err) => {
+                let (message, exit_code) = self.spawn_error(&err);
+                writeln!(error_writer, "{}", message).ok();
+                exit_code
             }
-        } else {
-            -1
         }
     }
 }
@@ -101,5 +278,109 @@ mod tests {
     fn test_find_in_path() {
         set_env_path();
         assert_eq!(find_in_path("ls"), Some(PathBuf::from("/usr/bin/ls")));
+        assert_eq!(
+            find_in_path("/usr/bin/ls"),
+            Some(PathBuf::from("/usr/bin/ls"))
+        );
+        assert_eq!(find_in_path("/usr/bin"), None);
+    }
+
+    #[test]
+    fn test_find_in_path_skips_cwd() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // 当前目录中的可执行文件不在 PATH 中，只能通过带 / 的路径找到
+        let name = format!("zzlocal-{}", process::id());
+        fs::write(&name, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&name, fs::Permissions::from_mode(0o755)).unwrap();
+        let bare = find_in_path(&name);
+        let relative = find_in_path(&format!("./{}", name));
+        let parsed = Executable::parse(&name, &[]).is_ok();
+        fs::remove_file(&name).ok();
+
+        assert_eq!(bare, None);
+        assert_eq!(relative, Some(PathBuf::from(format!("./{}", name))));
+        assert!(!parsed);
+    }
+
+    #[test]
+    fn test_hash_table() {
+        set_env_path();
+        let mut table = HashTable::default();
+        assert_eq!(table.get("ls"), None);
+
+        assert_eq!(table.lookup("ls"), Some(PathBuf::from("/usr/bin/ls")));
+        assert_eq!(table.lookup("ls"), Some(PathBuf::from("/usr/bin/ls")));
+        assert_eq!(table.get("ls").map(|hashed| hashed.hits), Some(2));
+        assert_eq!(table.lookup("invalid_command"), None);
+        assert_eq!(table.entries().len(), 1);
+
+        table.remember("list", PathBuf::from("/usr/bin/ls"));
+        assert_eq!(
+            table.get("list"),
+            Some(&HashedCommand {
+                path: PathBuf::from("/usr/bin/ls"),
+                hits: 0
+            })
+        );
+        assert!(table.forget("ls"));
+        assert!(!table.forget("ls"));
+    }
+
+    #[test]
+    fn test_execute_errors() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join("test_execute_errors");
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        let create_file = |name: &str, content: &[u8], mode: u32| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let output_file = dir.join("output.txt");
+        let error_file = dir.join("error.txt");
+        let run = |command: &str| {
+            let exit_code = Executable::parse(command, &[]).unwrap().execute(
+                Reader::Stdin,
+                fs::File::create(&output_file).unwrap().into(),
+                fs::File::create(&error_file).unwrap().into(),
+            );
+            let error = fs::read_to_string(&error_file).unwrap();
+            (exit_code, error.replace(dir.to_str().unwrap(), "."))
+        };
+
+        let ok = create_file("ok", b"#!/bin/sh\necho out\nexit 3\n", 0o755);
+        assert_eq!(run(&ok), (3, String::new()));
+        assert_eq!(fs::read_to_string(&output_file).unwrap(), "out\n");
+        assert_eq!(
+            run(&create_file("no_perm", b"#!/bin/sh\n", 0o644)),
+            (126, "./no_perm: Permission denied\n".to_string())
+        );
+        assert_eq!(
+            run(&create_file("no_shebang", b"echo hi\n", 0o755)),
+            (
+                126,
+                "./no_shebang: cannot execute: missing #! interpreter line\n".to_string()
+            )
+        );
+        assert_eq!(
+            run(&create_file("binary", b"\x7fELF\0\0", 0o755)),
+            (
+                126,
+                "./binary: cannot execute binary file: Exec format error\n".to_string()
+            )
+        );
+        assert_eq!(
+            run(&create_file("bad_interp", b"#!/nonexistent/sh\n", 0o755)),
+            (
+                126,
+                "./bad_interp: /nonexistent/sh: bad interpreter: No such file or directory\n"
+                    .to_string()
+            )
+        );
+        fs::remove_dir_all(&dir).ok();
     }
 }
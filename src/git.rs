@@ -0,0 +1,576 @@
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    // 工作目录 -> (.git 目录, 工作区根目录)，通过查找过的各级目录的 mtime 判断 .git 是否出现或消失
+    static ref GIT_DIR_CACHE: Mutex<HashMap<PathBuf, CachedRepo>> = Mutex::new(HashMap::new());
+    // .git 目录 -> 上一次计算的状态，通过 HEAD 和 index 的 mtime 以及缓存时间判断是否失效
+    static ref GIT_STATUS_CACHE: Mutex<HashMap<PathBuf, CachedStatus>> = Mutex::new(HashMap::new());
+}
+
+const INDEX_ENTRY_FIXED_SIZE: usize = 40;
+const CE_EXTENDED: u16 = 0x4000;
+const CE_SKIP_WORKTREE: u16 = 0x4000;
+const CE_INTENT_TO_ADD: u16 = 0x2000;
+const S_IFMT: u32 = 0o170000;
+const S_IFGITLINK: u32 = 0o160000;
+// 修改工作区的文件不会改变 index，只能通过过期时间重新检查
+const STATUS_CACHE_TTL: Duration = Duration::from_secs(2);
+// 检查工作区是否有修改需要 stat 每个文件，文件太多时不检查，避免拖慢提示符
+const MAX_DIRTY_CHECK_ENTRIES: usize = 5000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRepo {
+    pub git_dir: PathBuf,
+    pub work_tree: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitHead {
+    Branch(String),
+    Detached(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitStatus {
+    pub head: GitHead,
+    pub dirty: bool,
+    pub staged: bool,
+}
+
+impl GitStatus {
+    pub fn prompt_segment(&self) -> String {
+        let mut segment = match &self.head {
+            GitHead::Branch(branch) => branch.clone(),
+            GitHead::Detached(hash) => hash.clone(),
+        };
+        if self.dirty {
+            segment.push('*');
+        }
+        if self.staged {
+            segment.push('+');
+        }
+        format!("({})", segment)
+    }
+}
+
+struct CachedRepo {
+    dir_mtimes: Vec<Option<SystemTime>>,
+    repo: Option<GitRepo>,
+}
+
+struct CachedStatus {
+    head_mtime: Option<SystemTime>,
+    index_mtime: Option<SystemTime>,
+    checked_at: Instant,
+    status: Option<GitStatus>,
+}
+
+struct IndexEntry {
+    path: String,
+    mtime_sec: u32,
+    mtime_nsec: u32,
+    mode: u32,
+    size: u32,
+}
+
+struct Index {
+    entries: Vec<IndexEntry>,
+    // None 表示 index 中没有 TREE 扩展
+    cache_tree_valid: Option<bool>,
+}
+
+pub fn find_repo(start: &Path) -> Option<GitRepo> {
+    let mut cache = GIT_DIR_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(cached) = cache.get(start)
+        && cached.dir_mtimes == dir_mtimes(start, cached.repo.as_ref())
+    {
+        return cached.repo.clone();
+    }
+
+    let repo = start.ancestors().find_map(|dir| {
+        let dot_git = dir.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else if dot_git.is_file() {
+            // worktree 和 submodule 中的 .git 是一个文件，内容为 `gitdir: <path>`
+            let content = fs::read_to_string(&dot_git).ok()?;
+            dir.join(content.strip_prefix("gitdir:")?.trim())
+        } else {
+            return None;
+        };
+        git_dir.join("HEAD").is_file().then(|| GitRepo {
+            git_dir,
+            work_tree: dir.to_path_buf(),
+        })
+    });
+    cache.insert(
+        start.to_path_buf(),
+        CachedRepo {
+            dir_mtimes: dir_mtimes(start, repo.as_ref()),
+            repo: repo.clone(),
+        },
+    );
+    repo
+}
+
+// 从 start 到工作区根目录 (不在仓库中时到 /) 的各级目录的 mtime，创建或删除 .git 会改变所在目录的 mtime
+fn dir_mtimes(start: &Path, repo: Option<&GitRepo>) -> Vec<Option<SystemTime>> {
+    let mut mtimes = vec![];
+    for dir in start.ancestors() {
+        mtimes.push(mtime(dir));
+        if repo.is_some_and(|repo| repo.work_tree == dir) {
+            break;
+        }
+    }
+    mtimes
+}
+
+pub fn git_status(start: &Path) -> Option<GitStatus> {
+    let repo = find_repo(start)?;
+    let head_mtime = mtime(&repo.git_dir.join("HEAD"));
+    let index_mtime = mtime(&repo.git_dir.join("index"));
+
+    let mut cache = GIT_STATUS_CACHE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    if let Some(cached) = cache.get(&repo.git_dir)
+        && cached.head_mtime == head_mtime
+        && cached.index_mtime == index_mtime
+        && cached.checked_at.elapsed() < STATUS_CACHE_TTL
+    {
+        return cached.status.clone();
+    }
+
+    let status = read_status(&repo);
+    cache.insert(
+        repo.git_dir.clone(),
+        CachedStatus {
+            head_mtime,
+            index_mtime,
+            checked_at: Instant::now(),
+            status: status.clone(),
+        },
+    );
+    status
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn read_status(repo: &GitRepo) -> Option<GitStatus> {
+    let (head, head_commit) = read_head(&repo.git_dir)?;
+    let (dirty, staged) = match fs::read(repo.git_dir.join("index"))
+        .ok()
+        .and_then(|data| parse_index(&data, hash_size(&repo.git_dir)))
+    {
+        Some(index) => (
+            is_worktree_dirty(&repo.work_tree, &index),
+            is_index_staged(&index, head_commit.is_some()),
+        ),
+        None => (false, false),
+    };
+    Some(GitStatus {
+        head,
+        dirty,
+        staged,
+    })
+}
+
+fn is_index_staged(index: &Index, has_commit: bool) -> bool {
+    match (has_commit, index.cache_tree_valid) {
+        // 还没有提交时 index 中的内容都是新暂存的
+        (false, _) => !index.entries.is_empty(),
+        // TREE 扩展在 `git add` 等修改 index 的操作后会被置为无效，`git commit` 后重新生成
+        (true, Some(valid)) => !valid,
+        // 没有 TREE 扩展时不读取对象就无法判断，不显示
+        (true, None) => false,
+    }
+}
+
+fn is_commit_hash(hash: &str) -> bool {
+    hash.len() >= 40 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// 返回 HEAD 和它指向的提交，新仓库中分支还没有提交时为 None
+fn read_head(git_dir: &Path) -> Option<(GitHead, Option<String>)> {
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    if let Some(reference) = head.strip_prefix("ref:") {
+        let reference = reference.trim();
+        let branch = reference.strip_prefix("refs/heads/").unwrap_or(reference);
+        Some((
+            GitHead::Branch(branch.to_string()),
+            resolve_ref(git_dir, reference),
+        ))
+    } else if is_commit_hash(head) {
+        // 分离的 HEAD 指向某个标签时显示标签名
+        let name = tag_of(git_dir, head).unwrap_or_else(|| head[..7].to_string());
+        Some((GitHead::Detached(name), Some(head.to_string())))
+    } else {
+        None
+    }
+}
+
+// (提交, 引用名)，附注标签下一行 ^ 开头的是它指向的提交
+fn packed_refs(git_dir: &Path) -> Vec<(String, String)> {
+    let content = fs::read_to_string(common_dir(git_dir).join("packed-refs")).unwrap_or_default();
+    let mut refs: Vec<(String, String)> = vec![];
+    for line in content.lines() {
+        if let Some(peeled) = line.strip_prefix('^') {
+            if let Some((_, name)) = refs.last() {
+                refs.push((peeled.to_string(), name.clone()));
+            }
+        } else if let Some((hash, name)) = line.split_once(' ')
+            && !line.starts_with('#')
+        {
+            refs.push((hash.to_string(), name.to_string()));
+        }
+    }
+    refs
+}
+
+// 先读取单独的引用文件，再查找 packed-refs，worktree 中共享的引用在 commondir 下
+fn resolve_ref(git_dir: &Path, reference: &str) -> Option<String> {
+    for dir in [git_dir.to_path_buf(), common_dir(git_dir)] {
+        if let Ok(hash) = fs::read_to_string(dir.join(reference)) {
+            let hash = hash.trim();
+            return is_commit_hash(hash).then(|| hash.to_string());
+        }
+    }
+    packed_refs(git_dir)
+        .into_iter()
+        .find(|(_, name)| name == reference)
+        .map(|(hash, _)| hash)
+}
+
+fn tag_of(git_dir: &Path, commit: &str) -> Option<String> {
+    let tags_dir = common_dir(git_dir).join("refs/tags");
+    let loose = fs::read_dir(&tags_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let hash = fs::read_to_string(entry.path()).ok()?;
+            Some((
+                hash.trim().to_string(),
+                format!("refs/tags/{}", entry.file_name().to_string_lossy()),
+            ))
+        });
+    loose
+        .chain(packed_refs(git_dir))
+        .find(|(hash, name)| hash == commit && name.starts_with("refs/tags/"))
+        .map(|(_, name)| name["refs/tags/".len()..].to_string())
+}
+
+fn common_dir(git_dir: &Path) -> PathBuf {
+    fs::read_to_string(git_dir.join("commondir"))
+        .map(|dir| git_dir.join(dir.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf())
+}
+
+fn hash_size(git_dir: &Path) -> usize {
+    let config = fs::read_to_string(common_dir(git_dir).join("config")).unwrap_or_default();
+    let is_sha256 = config.lines().any(|line| {
+        let line: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+        line.eq_ignore_ascii_case("objectformat=sha256")
+    });
+    if is_sha256 { 32 } else { 20 }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2)
+        .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+// https://git-scm.com/docs/index-format
+fn parse_index(data: &[u8], hash_size: usize) -> Option<Index> {
+    if data.get(0..4)? != b"DIRC" {
+        return None;
+    }
+    let version = read_u32(data, 4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let entry_num = read_u32(data, 8)? as usize;
+
+    let mut pos = 12;
+    let mut entries = Vec::with_capacity(entry_num);
+    let mut last_path: Vec<u8> = vec![];
+    for _ in 0..entry_num {
+        let entry_start = pos;
+        let mtime_sec = read_u32(data, pos + 8)?;
+        let mtime_nsec = read_u32(data, pos + 12)?;
+        let mode = read_u32(data, pos + 24)?;
+        let size = read_u32(data, pos + 36)?;
+        pos += INDEX_ENTRY_FIXED_SIZE + hash_size;
+
+        let flags = read_u16(data, pos)?;
+        pos += 2;
+        let extended_flags = if version >= 3 && flags & CE_EXTENDED != 0 {
+            pos += 2;
+            read_u16(data, pos - 2)?
+        } else {
+            0
+        };
+
+        let path = if version == 4 {
+            // v4 中 path 以前一个 entry 的 path 为前缀压缩存储
+            let mut strip_len = 0usize;
+            loop {
+                let byte = *data.get(pos)?;
+                pos += 1;
+                strip_len = (strip_len << 7) | (byte & 0x7f) as usize;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                strip_len += 1;
+            }
+            let name_len = data.get(pos..)?.iter().position(|&b| b == 0)?;
+            let mut path = last_path[..last_path.len().checked_sub(strip_len)?].to_vec();
+            path.extend_from_slice(&data[pos..pos + name_len]);
+            pos += name_len + 1;
+            path
+        } else {
+            let name_len = data.get(pos..)?.iter().position(|&b| b == 0)?;
+            let path = data[pos..pos + name_len].to_vec();
+            // entry 长度需要用 1~8 个 NUL 补齐到 8 的倍数
+            let entry_len = pos + name_len - entry_start;
+            pos = entry_start + (entry_len + 8) / 8 * 8;
+            path
+        };
+
+        if extended_flags & (CE_SKIP_WORKTREE | CE_INTENT_TO_ADD) == 0 {
+            entries.push(IndexEntry {
+                path: String::from_utf8_lossy(&path).to_string(),
+                mtime_sec,
+                mtime_nsec,
+                mode,
+                size,
+            });
+        }
+        last_path = path;
+    }
+
+    let mut cache_tree_valid = None;
+    // 最后 hash_size 个字节是整个 index 的校验和
+    while pos + 8 <= data.len().saturating_sub(hash_size) {
+        let signature = &data[pos..pos + 4];
+        let ext_size = read_u32(data, pos + 4)? as usize;
+        pos += 8;
+        if signature == b"TREE" {
+            // 第一个 entry 是根目录: "\0<entry_count> <subtrees>\n"
+            let ext = data.get(pos..pos + ext_size)?;
+            let count_end = ext.iter().position(|&b| b == b' ')?;
+            let entry_count = std::str::from_utf8(ext.get(1..count_end)?).ok()?;
+            cache_tree_valid = Some(!entry_count.starts_with('-'));
+        }
+        pos += ext_size;
+    }
+
+    Some(Index {
+        entries,
+        cache_tree_valid,
+    })
+}
+
+// 遇到第一个修改过的文件就返回
+fn is_worktree_dirty(work_tree: &Path, index: &Index) -> bool {
+    if index.entries.len() > MAX_DIRTY_CHECK_ENTRIES {
+        return false;
+    }
+    index.entries.iter().any(|entry| {
+        if entry.mode & S_IFMT == S_IFGITLINK {
+            return false;
+        }
+        match fs::symlink_metadata(work_tree.join(&entry.path)) {
+            Ok(meta) => {
+                meta.mtime() as u32 != entry.mtime_sec
+                    || meta.mtime_nsec() as u32 != entry.mtime_nsec
+                    || meta.size() as u32 != entry.size
+            }
+            Err(_) => true,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_entry(path: &str, flags_extra: u16) -> Vec<u8> {
+        let mut entry = vec![0u8; INDEX_ENTRY_FIXED_SIZE + 20];
+        entry[24..28].copy_from_slice(&0o100644u32.to_be_bytes());
+        entry[36..40].copy_from_slice(&5u32.to_be_bytes());
+        entry.extend_from_slice(&(path.len() as u16 | flags_extra).to_be_bytes());
+        entry.extend_from_slice(path.as_bytes());
+        let padding = 8 - entry.len() % 8;
+        entry.extend(std::iter::repeat_n(0, padding));
+        entry
+    }
+
+    fn build_index(paths: &[&str], tree_entry_count: Option<&str>) -> Vec<u8> {
+        let mut data = b"DIRC".to_vec();
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&(paths.len() as u32).to_be_bytes());
+        for path in paths {
+            data.extend(index_entry(path, 0));
+        }
+        if let Some(count) = tree_entry_count {
+            let ext = format!("\0{} 0\n", count);
+            data.extend_from_slice(b"TREE");
+            data.extend_from_slice(&(ext.len() as u32).to_be_bytes());
+            data.extend_from_slice(ext.as_bytes());
+        }
+        data.extend(std::iter::repeat_n(0, 20));
+        data
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let index = parse_index(&build_index(&["Cargo.toml", "src/main.rs"], None), 20).unwrap();
+        assert_eq!(
+            index
+                .entries
+                .iter()
+                .map(|entry| entry.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Cargo.toml", "src/main.rs"]
+        );
+        assert_eq!(index.entries[1].size, 5);
+        assert_eq!(index.cache_tree_valid, None);
+
+        let index = parse_index(&build_index(&["a"], Some("1")), 20).unwrap();
+        assert_eq!(index.cache_tree_valid, Some(true));
+        let index = parse_index(&build_index(&["a"], Some("-1")), 20).unwrap();
+        assert_eq!(index.cache_tree_valid, Some(false));
+
+        assert!(parse_index(b"XXXX", 20).is_none());
+    }
+
+    #[test]
+    fn test_read_head() {
+        let git_dir =
+            std::env::temp_dir().join(format!("test_git_read_head-{}", std::process::id()));
+        fs::remove_dir_all(&git_dir).ok();
+        fs::create_dir_all(git_dir.join("refs/heads/feature")).unwrap();
+        fs::create_dir_all(git_dir.join("refs/tags")).unwrap();
+        let commit = "0b5407e1c3a0a9d4a7c0f6e2b8c1d2e3f4a5b6c7";
+        let tagged = "1111111111111111111111111111111111111111";
+
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/feature/prompt\n").unwrap();
+        let branch = GitHead::Branch("feature/prompt".to_string());
+        assert_eq!(read_head(&git_dir), Some((branch.clone(), None)));
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted\n\
+                 {commit} refs/heads/feature/prompt\n\
+                 2222222222222222222222222222222222222222 refs/tags/v1.0\n\
+                 ^{tagged}\n"
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            read_head(&git_dir),
+            Some((branch.clone(), Some(commit.to_string())))
+        );
+        fs::write(
+            git_dir.join("refs/heads/feature/prompt"),
+            format!("{tagged}\n"),
+        )
+        .unwrap();
+        assert_eq!(
+            read_head(&git_dir),
+            Some((branch, Some(tagged.to_string())))
+        );
+
+        fs::write(git_dir.join("HEAD"), format!("{commit}\n")).unwrap();
+        assert_eq!(
+            read_head(&git_dir),
+            Some((
+                GitHead::Detached("0b5407e".to_string()),
+                Some(commit.to_string())
+            ))
+        );
+        fs::write(git_dir.join("HEAD"), format!("{tagged}\n")).unwrap();
+        assert_eq!(
+            read_head(&git_dir).map(|(head, _)| head),
+            Some(GitHead::Detached("v1.0".to_string()))
+        );
+        fs::write(git_dir.join("refs/tags/v2.0"), format!("{commit}\n")).unwrap();
+        fs::write(git_dir.join("HEAD"), format!("{commit}\n")).unwrap();
+        assert_eq!(
+            read_head(&git_dir).map(|(head, _)| head),
+            Some(GitHead::Detached("v2.0".to_string()))
+        );
+        fs::remove_dir_all(&git_dir).ok();
+    }
+
+    #[test]
+    fn test_index_staged() {
+        let index =
+            |tree_entry_count| parse_index(&build_index(&["a"], tree_entry_count), 20).unwrap();
+        assert!(is_index_staged(&index(None), false));
+        assert!(!is_index_staged(&index(None), true));
+        assert!(!is_index_staged(&index(Some("1")), true));
+        assert!(is_index_staged(&index(Some("-1")), true));
+    }
+
+    #[test]
+    fn test_worktree_dirty() {
+        let work_tree = std::env::temp_dir().join(format!("test_git_dirty-{}", std::process::id()));
+        let index = |count| Index {
+            entries: (0..count)
+                .map(|idx| IndexEntry {
+                    path: format!("missing-{}", idx),
+                    mtime_sec: 0,
+                    mtime_nsec: 0,
+                    mode: 0o100644,
+                    size: 0,
+                })
+                .collect(),
+            cache_tree_valid: None,
+        };
+        assert!(!is_worktree_dirty(&work_tree, &index(0)));
+        assert!(is_worktree_dirty(&work_tree, &index(1)));
+        assert!(!is_worktree_dirty(
+            &work_tree,
+            &index(MAX_DIRTY_CHECK_ENTRIES + 1)
+        ));
+    }
+
+    #[test]
+    fn test_find_repo_after_init() {
+        let root = std::env::temp_dir().join(format!("test_git_find_repo-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        let work_dir = root.join("src");
+        fs::create_dir_all(&work_dir).unwrap();
+        assert_eq!(find_repo(&work_dir), None);
+
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert_eq!(
+            find_repo(&work_dir).map(|repo| repo.work_tree),
+            Some(root.clone())
+        );
+
+        fs::remove_dir_all(root.join(".git")).unwrap();
+        assert_eq!(find_repo(&work_dir), None);
+        fs::remove_dir_all(&root).ok();
+    }
+}
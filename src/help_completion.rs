@@ -0,0 +1,191 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    process,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{HISTORY_FILE, utils::read_command_output};
+
+lazy_static! {
+    // 与历史记录文件放在同一目录下
+    static ref HELP_CACHE_DIR: PathBuf = Path::new(HISTORY_FILE.as_str())
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(".completion_cache");
+    static ref HELP_CACHE: Mutex<HashMap<PathBuf, (u128, HelpCompletion)>> =
+        Mutex::new(HashMap::new());
+    static ref LONG_FLAG_RE: Regex =
+        Regex::new(r"(?:^|[\s,\[(])(--[A-Za-z0-9][A-Za-z0-9_-]*)").unwrap();
+    static ref SUBCOMMAND_RE: Regex =
+        Regex::new(r"^\s+([a-z][a-z0-9_-]*)((?:,\s*[a-z][a-z0-9_-]*)*)(?:\s|$)").unwrap();
+}
+
+const HELP_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HelpCompletion {
+    pub flags: BTreeSet<String>,
+    pub subcommands: BTreeSet<String>,
+}
+
+pub fn help_completion(exec_path: &Path) -> Option<HelpCompletion> {
+    let mtime = fs::metadata(exec_path)
+        .and_then(|meta| meta.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+
+    let mut cache = HELP_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some((cached_mtime, completion)) = cache.get(exec_path)
+        && *cached_mtime == mtime
+    {
+        return Some(completion.clone());
+    }
+
+    let cache_file = HELP_CACHE_DIR.join(cache_file_name(exec_path));
+    let completion = match load_cache_file(&cache_file, mtime) {
+        Some(completion) => completion,
+        None => {
+            let help = read_command_output(
+                process::Command::new(exec_path)
+                    .arg("--help")
+                    .env("PAGER", "cat")
+                    .env("MANPAGER", "cat")
+                    .env("NO_COLOR", "1"),
+                HELP_TIMEOUT,
+            )
+            .unwrap_or_default();
+            let completion = parse_help(&help);
+            // 即使解析不出任何内容也写入缓存，避免每次补全都重新运行命令
+            save_cache_file(&cache_file, mtime, &completion);
+            completion
+        }
+    };
+    cache.insert(exec_path.to_path_buf(), (mtime, completion.clone()));
+    Some(completion)
+}
+
+fn cache_file_name(exec_path: &Path) -> String {
+    exec_path
+        .to_string_lossy()
+        .replace('%', "%%")
+        .replace('/', "%")
+}
+
+// 缓存文件格式: 第一行为可执行文件的 mtime，之后每一行是一个候选项，以 - 开头的是 flag
+fn load_cache_file(cache_file: &Path, mtime: u128) -> Option<HelpCompletion> {
+    let content = fs::read_to_string(cache_file).ok()?;
+    let mut lines = content.lines();
+    if lines.next()?.parse::<u128>().ok()? != mtime {
+        return None;
+    }
+    let mut completion = HelpCompletion::default();
+    for line in lines.filter(|line| !line.is_empty()) {
+        if line.starts_with('-') {
+            completion.flags.insert(line.to_string());
+        } else {
+            completion.subcommands.insert(line.to_string());
+        }
+    }
+    Some(completion)
+}
+
+fn save_cache_file(cache_file: &Path, mtime: u128, completion: &HelpCompletion) {
+    let content = completion
+        .flags
+        .iter()
+        .chain(completion.subcommands.iter())
+        .fold(mtime.to_string() + "\n", |acc, word| acc + word + "\n");
+    if let Some(dir) = cache_file.parent() {
+        fs::create_dir_all(dir).ok();
+    }
+    // 先写临时文件再 rename，避免多个 shell 同时写入时读到不完整的内容
+    let tmp_file = cache_file.with_extension(format!(
+        "tmp{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos())
+    ));
+    if fs::write(&tmp_file, content).is_ok() && fs::rename(&tmp_file, cache_file).is_err() {
+        fs::remove_file(&tmp_file).ok();
+    }
+}
+
+fn is_subcommand_header(line: &str) -> bool {
+    let line = line.trim().to_lowercase();
+    !line.starts_with('-') && line.contains("command") && line.ends_with(':')
+}
+
+pub fn parse_help(help: &str) -> HelpCompletion {
+    let mut completion = HelpCompletion::default();
+    let mut in_subcommands = false;
+    for line in help.lines() {
+        for caps in LONG_FLAG_RE.captures_iter(line) {
+            completion.flags.insert(caps[1].to_string());
+        }
+
+        if is_subcommand_header(line) {
+            in_subcommands = true;
+        } else if line.trim().is_empty() {
+            continue;
+        } else if !line.starts_with(char::is_whitespace) {
+            in_subcommands = false;
+        } else if in_subcommands && let Some(caps) = SUBCOMMAND_RE.captures(line) {
+            completion.subcommands.insert(caps[1].to_string());
+            for alias in caps[2].split(',').map(|alias| alias.trim()) {
+                if !alias.is_empty() {
+                    completion.subcommands.insert(alias.to_string());
+                }
+            }
+        }
+    }
+    completion
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_help() {
+        let help = "\
+Usage: cargo [OPTIONS] [COMMAND]
+
+Options:
+  -V, --version                  Print version info and exit
+      --list                     List installed commands
+      --color <WHEN>             Coloring
+  -h, --help                     Print help
+
+Commands:
+    build, b    Compile the current package
+    clean       Remove the target directory
+    ...         See all commands with --list
+
+See 'cargo help <command>' for more information on a specific command.
+";
+        let completion = parse_help(help);
+        assert_eq!(
+            completion.flags.into_iter().collect::<Vec<_>>(),
+            vec!["--color", "--help", "--list", "--version"]
+        );
+        assert_eq!(
+            completion.subcommands.into_iter().collect::<Vec<_>>(),
+            vec!["b", "build", "clean"]
+        );
+
+        let completion = parse_help("  -t TYPE, --type=TYPE\n      --type-list\n");
+        assert_eq!(
+            completion.flags.into_iter().collect::<Vec<_>>(),
+            vec!["--type", "--type-list"]
+        );
+        assert!(completion.subcommands.is_empty());
+    }
+}
@@ -1,6 +1,9 @@
-use rustyline::{Completer, Helper, Highlighter, Hinter, Validator, hint::HistoryHinter};
+use rustyline::{Completer, Helper, Highlighter, Hinter, Validator};
 
-use crate::{completer::ShellCompleter, validator::ShellValidator};
+use crate::{
+    completer::ShellCompleter, highlighter::ShellHighlighter, hinter::ShellHinter,
+    validator::ShellValidator,
+};
 
 #[derive(Helper, Completer, Hinter, Highlighter, Validator)]
 pub struct ShellHelper {
@@ -12,7 +15,10 @@ pub struct ShellHelper {
     completer: ShellCompleter,
 
     #[rustyline(Hinter)]
-    hinter: HistoryHinter,
+    hinter: ShellHinter,
+
+    #[rustyline(Highlighter)]
+    highlighter: ShellHighlighter,
 }
 
 impl ShellHelper {
@@ -20,7 +26,8 @@ impl ShellHelper {
         Self {
             validator: ShellValidator,
             completer: ShellCompleter,
-            hinter: HistoryHinter::new(),
+            hinter: ShellHinter::new(),
+            highlighter: ShellHighlighter::new(),
         }
     }
 }
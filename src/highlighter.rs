@@ -0,0 +1,340 @@
+use std::{borrow::Cow, cell::Cell, env};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use rustyline::highlight::{CmdKind, Highlighter};
+
+use crate::{
+    builtin::{BUILTIN_COMMANDS, SHELL_KEYWORDS},
+    command_index::is_indexed_command,
+    executable::find_in_path,
+    tokenize::ASSIGNMENT_RE,
+};
+
+lazy_static! {
+    static ref REDIRECT_RE: Regex = Regex::new(r"^[0-9]*(?:>>?|<)(?:&[0-9]+)?").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Command,
+    Error,
+    String,
+    Variable,
+    Operator,
+    Redirect,
+    Bracket,
+}
+
+impl Style {
+    fn name(&self) -> &'static str {
+        match self {
+            Style::Command => "command",
+            Style::Error => "error",
+            Style::String => "string",
+            Style::Variable => "variable",
+            Style::Operator => "operator",
+            Style::Redirect => "redirect",
+            Style::Bracket => "bracket",
+        }
+    }
+
+    fn default_color(&self) -> &'static str {
+        match self {
+            Style::Command => "32",
+            Style::Error => "31",
+            Style::String => "33",
+            Style::Variable => "36",
+            Style::Operator | Style::Redirect => "35",
+            Style::Bracket => "1;34",
+        }
+    }
+
+    // HIGHLIGHT_COLORS 的格式为 command=1;32:string=33，值为空时不着色，没有设置的使用默认颜色
+    fn color(&self, config: &str) -> String {
+        config
+            .split(':')
+            .rev()
+            .filter_map(|item| item.split_once('='))
+            .find(|(name, _)| *name == self.name())
+            .map(|(_, color)| color)
+            .filter(|color| color.chars().all(|c| c.is_ascii_digit() || c == ';'))
+            .unwrap_or(self.default_color())
+            .to_string()
+    }
+}
+
+fn is_operator(c: char) -> bool {
+    matches!(c, '|' | '&' | ';')
+}
+
+// 每次按键都会调用，不带 / 的命令只查询命令索引，不扫描 PATH
+fn is_known_command(word: &str) -> bool {
+    if word.contains('/') {
+        return find_in_path(word).is_some();
+    }
+    BUILTIN_COMMANDS.contains(word) || SHELL_KEYWORDS.contains(word) || is_indexed_command(word)
+}
+
+// $name, ${...}, $? 这样的变量，返回变量之后的位置，$( 交给括号处理
+fn scan_variable(chars: &[char], pos: usize, limit: usize, styles: &mut [Option<Style>]) -> usize {
+    let end = match chars[..limit].get(pos + 1) {
+        Some('{') => chars[pos..limit]
+            .iter()
+            .position(|&c| c == '}')
+            .map_or(limit, |offset| pos + offset + 1),
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            pos + 1
+                + chars[pos + 1..limit]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count()
+        }
+        Some(c) if c.is_ascii_digit() || "?$#!@*-".contains(*c) => pos + 2,
+        _ => return pos + 1,
+    };
+    styles[pos..end].fill(Some(Style::Variable));
+    end
+}
+
+// 引号中的内容，没有闭合的引号一直标记到行尾
+fn scan_quote(chars: &[char], pos: usize, styles: &mut [Option<Style>]) -> usize {
+    let quote = chars[pos];
+    let mut end = pos + 1;
+    while end < chars.len() && chars[end] != quote {
+        end += if quote == '"' && chars[end] == '\\' {
+            2
+        } else {
+            1
+        };
+    }
+    if end >= chars.len() {
+        styles[pos..].fill(Some(Style::Error));
+        return chars.len();
+    }
+    styles[pos..=end].fill(Some(Style::String));
+    if quote == '"' {
+        let mut idx = pos + 1;
+        while idx < end {
+            idx = match chars[idx] {
+                '\\' => idx + 2,
+                '$' => scan_variable(chars, idx, end, styles),
+                _ => idx + 1,
+            };
+        }
+    }
+    end + 1
+}
+
+// 返回单词结束的位置
+fn scan_word(chars: &[char], pos: usize, styles: &mut [Option<Style>]) -> usize {
+    let mut pos = pos;
+    while pos < chars.len() {
+        pos = match chars[pos] {
+            c if c.is_whitespace() || is_operator(c) => break,
+            '\\' => pos + 2,
+            '\'' | '"' => scan_quote(chars, pos, styles),
+            '$' => scan_variable(chars, pos, chars.len(), styles),
+            _ => pos + 1,
+        };
+    }
+    pos.min(chars.len())
+}
+
+fn classify(chars: &[char]) -> Vec<Option<Style>> {
+    let mut styles = vec![None; chars.len()];
+    let mut pos = 0;
+    // 行首和操作符之后的第一个单词 (跳过变量赋值) 是命令
+    let mut command_position = true;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+        if is_operator(c) {
+            let len = if c != ';' && chars.get(pos + 1) == Some(&c) {
+                2
+            } else {
+                1
+            };
+            styles[pos..pos + len].fill(Some(Style::Operator));
+            pos += len;
+            command_position = true;
+            continue;
+        }
+        let rest: String = chars[pos..].iter().collect();
+        if let Some(redirect) = REDIRECT_RE.find(&rest) {
+            let end = pos + redirect.as_str().chars().count();
+            styles[pos..end].fill(Some(Style::Redirect));
+            pos = end;
+            continue;
+        }
+
+        let end = scan_word(chars, pos, &mut styles);
+        if command_position {
+            let word: String = chars[pos..end]
+                .iter()
+                .filter(|c| !matches!(c, '\'' | '"' | '\\'))
+                .collect();
+            if !ASSIGNMENT_RE.is_match(&word) {
+                let style = if is_known_command(&word) {
+                    Style::Command
+                } else {
+                    Style::Error
+                };
+                for slot in styles[pos..end].iter_mut().filter(|slot| slot.is_none()) {
+                    *slot = Some(style);
+                }
+                command_position = false;
+            }
+        }
+        pos = end;
+    }
+    styles
+}
+
+// 光标所在或者光标前的括号及与之匹配的括号，引号中的括号不参与匹配
+fn matching_brackets(
+    chars: &[char],
+    styles: &[Option<Style>],
+    cursor: usize,
+) -> Option<(usize, usize)> {
+    let mut pairs = vec![];
+    let mut stack = vec![];
+    for (idx, c) in chars.iter().enumerate() {
+        if matches!(styles[idx], Some(Style::String | Style::Error)) {
+            continue;
+        }
+        match c {
+            '(' | '[' | '{' => stack.push((idx, *c)),
+            ')' | ']' | '}' => {
+                if let Some((open, open_char)) = stack.pop()
+                    && matches!((open_char, c), ('(', ')') | ('[', ']') | ('{', '}'))
+                {
+                    pairs.push((open, idx));
+                }
+            }
+            _ => {}
+        }
+    }
+    [Some(cursor), cursor.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .find_map(|idx| {
+            pairs
+                .iter()
+                .find(|(open, close)| *open == idx || *close == idx)
+                .copied()
+        })
+}
+
+#[derive(Default)]
+pub struct ShellHighlighter {
+    // 提交命令时的最后一次刷新不显示括号匹配
+    show_brackets: Cell<bool>,
+}
+
+impl ShellHighlighter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Highlighter for ShellHighlighter {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut styles = classify(&chars);
+        if self.show_brackets.get() {
+            let cursor = line[..pos].chars().count();
+            if let Some((open, close)) = matching_brackets(&chars, &styles, cursor) {
+                styles[open] = Some(Style::Bracket);
+                styles[close] = Some(Style::Bracket);
+            }
+        }
+
+        let config = env::var("HIGHLIGHT_COLORS").unwrap_or_default();
+        let mut highlighted = String::new();
+        let mut idx = 0;
+        while idx < chars.len() {
+            let style = styles[idx];
+            let run = styles[idx..].iter().take_while(|s| **s == style).count();
+            let text: String = chars[idx..idx + run].iter().collect();
+            match style.map(|style| style.color(&config)) {
+                Some(color) if !color.is_empty() => {
+                    highlighted.push_str(&format!("\x1b[{}m{}\x1b[0m", color, text))
+                }
+                _ => highlighted.push_str(&text),
+            }
+            idx += run;
+        }
+        Cow::Owned(highlighted)
+    }
+
+    // 命令是否存在会随着每次输入变化，所以总是需要重新绘制
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        self.show_brackets.set(kind != CmdKind::ForcedRefresh);
+        kind != CmdKind::ForcedRefresh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(line: &str) -> String {
+        let chars: Vec<char> = line.chars().collect();
+        classify(&chars)
+            .into_iter()
+            .map(|style| match style {
+                None => '.',
+                Some(Style::Command) => 'c',
+                Some(Style::Error) => 'x',
+                Some(Style::String) => 's',
+                Some(Style::Variable) => 'v',
+                Some(Style::Operator) => 'o',
+                Some(Style::Redirect) => 'r',
+                Some(Style::Bracket) => 'b',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(summary("echo $HOME"), "cccc.vvvvv");
+        assert_eq!(summary("nosuchcmd a"), "xxxxxxxxx..");
+        assert_eq!(summary("ls /bin/ls /no/ls"), "cc...............");
+        assert_eq!(summary("/bin/ls;/no/ls"), "cccccccoxxxxxx");
+        assert_eq!(summary("echo 'a' \"$b c\""), "cccc.sss.svvsss");
+        assert_eq!(summary("echo a|pwd&&cd"), "cccc..occcoocc");
+        assert_eq!(summary("echo a 2>>f >&2"), "cccc...rrr..rrr");
+        assert_eq!(summary("A=1 echo ${x}$?"), "....cccc.vvvvvv");
+        assert_eq!(summary("echo 'abc"), "cccc.xxxx");
+        assert_eq!(summary("echo a # b"), "cccc......");
+        assert_eq!(summary("echo a\\$b"), "cccc.....");
+    }
+
+    #[test]
+    fn test_matching_brackets() {
+        let find = |line: &str, cursor| {
+            let chars: Vec<char> = line.chars().collect();
+            let styles = classify(&chars);
+            matching_brackets(&chars, &styles, cursor)
+        };
+        assert_eq!(find("echo $((1+(2)))", 6), Some((6, 14)));
+        assert_eq!(find("echo $((1+(2)))", 13), Some((7, 13)));
+        assert_eq!(find("echo $((1+(2)))", 11), Some((10, 12)));
+        assert_eq!(find("echo $((1+(2)))", 15), Some((6, 14)));
+        assert_eq!(find("echo $((1+(2)))", 2), None);
+        assert_eq!(find("echo '(' )", 6), None);
+        assert_eq!(find("echo ( ]", 5), None);
+    }
+
+    #[test]
+    fn test_style_color() {
+        assert_eq!(Style::Command.color(""), "32");
+        assert_eq!(Style::Command.color("command=1;32:string=33"), "1;32");
+        assert_eq!(Style::String.color("string=:variable=2"), "");
+        assert_eq!(Style::Variable.color("variable=bold"), "36");
+    }
+}
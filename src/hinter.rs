@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+};
+
+use rustyline::{
+    Context,
+    hint::{Hinter, HistoryHinter},
+};
+
+use crate::{
+    directory::logical_cwd, git::find_repo, history::HistoryEntry, history_search::SEARCH_HISTORY,
+};
+
+// HINT_MODE=directory 时优先提示在当前目录执行过的命令，其次是同一个 git 仓库中的，
+// 最后才是全局历史，并且在仓库中时不会提示其他仓库中执行的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HintMode {
+    Global,
+    Directory,
+}
+
+impl HintMode {
+    pub fn current() -> Self {
+        match env::var("HINT_MODE").as_deref() {
+            Ok("directory") => HintMode::Directory,
+            _ => HintMode::Global,
+        }
+    }
+}
+
+fn repo_root(dir: &Path) -> Option<PathBuf> {
+    find_repo(dir).map(|repo| repo.work_tree)
+}
+
+// 数字越小越优先，None 表示这条记录属于其他仓库
+// 每次按键都会计算，所以同一个目录的仓库根目录只查找一次，缓存在 repo_roots 中
+fn entry_scope<'a>(
+    entry: &'a HistoryEntry,
+    cwd: &Path,
+    repo: Option<&Path>,
+    repo_roots: &mut HashMap<&'a str, Option<PathBuf>>,
+) -> Option<u8> {
+    let Some(entry_cwd) = entry.metadata.cwd.as_deref() else {
+        return Some(2);
+    };
+    if Path::new(entry_cwd) == cwd {
+        return Some(0);
+    }
+    // 不在仓库中时不需要区分其他目录的记录
+    let Some(repo) = repo else {
+        return Some(2);
+    };
+    let entry_repo = repo_roots
+        .entry(entry_cwd)
+        .or_insert_with(|| repo_root(Path::new(entry_cwd)));
+    match entry_repo {
+        Some(entry_repo) if entry_repo == repo => Some(1),
+        Some(_) => None,
+        None => Some(2),
+    }
+}
+
+// 在范围最小的记录中取最近的一条
+fn scoped_hint(line: &str, entries: &[HistoryEntry], cwd: &Path) -> Option<String> {
+    let repo = repo_root(cwd);
+    let mut repo_roots = HashMap::new();
+    let mut best: Option<(u8, &str)> = None;
+    for entry in entries.iter().rev() {
+        if !entry.line.starts_with(line) || entry.line.len() == line.len() {
+            continue;
+        }
+        let Some(scope) = entry_scope(entry, cwd, repo.as_deref(), &mut repo_roots) else {
+            continue;
+        };
+        if best.is_none_or(|(best_scope, _)| scope < best_scope) {
+            best = Some((scope, &entry.line));
+        }
+        if scope == 0 {
+            break;
+        }
+    }
+    best.map(|(_, hint)| hint[line.len()..].to_string())
+}
+
+pub struct ShellHinter {
+    history: HistoryHinter,
+}
+
+impl ShellHinter {
+    pub fn new() -> Self {
+        Self {
+            history: HistoryHinter::new(),
+        }
+    }
+}
+
+impl Hinter for ShellHinter {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+        if HintMode::current() == HintMode::Global {
+            return self.history.hint(line, pos, ctx);
+        }
+        if line.is_empty() || pos < line.len() {
+            return None;
+        }
+        // 记录中保存的是逻辑路径，比较时也要用逻辑路径，否则经过符号链接的目录永远匹配不上
+        scoped_hint(line, &SEARCH_HISTORY.lock().ok()?, &logical_cwd())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::history::EntryMetadata;
+
+    use super::*;
+
+    fn entry(line: &str, cwd: Option<&Path>) -> HistoryEntry {
+        HistoryEntry {
+            line: line.to_string(),
+            metadata: EntryMetadata {
+                cwd: cwd.map(|cwd| cwd.display().to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_scoped_hint() {
+        let root = env::temp_dir().join(format!("hinter-test-{}", std::process::id()));
+        let (service, other, plain) = (root.join("service"), root.join("other"), root.join("tmp"));
+        for repo in [&service, &other] {
+            fs::create_dir_all(repo.join(".git")).unwrap();
+            fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        }
+        let service_src = service.join("src");
+        fs::create_dir_all(&service_src).unwrap();
+        fs::create_dir_all(&plain).unwrap();
+
+        let entries = vec![
+            entry("cargo build", None),
+            entry("cargo test --lib", Some(&service)),
+            entry("cargo run", Some(&service_src)),
+            entry("cargo fmt", Some(&other)),
+            entry("make deploy", Some(&other)),
+            entry("make", Some(&plain)),
+        ];
+        assert_eq!(
+            scoped_hint("cargo ", &entries, &service_src).as_deref(),
+            Some("run")
+        );
+        assert_eq!(
+            scoped_hint("cargo t", &entries, &service_src).as_deref(),
+            Some("est --lib")
+        );
+        // 不会提示其他仓库中的命令，但会退回到全局历史
+        assert_eq!(
+            scoped_hint("cargo b", &entries, &service).as_deref(),
+            Some("uild")
+        );
+        assert_eq!(scoped_hint("make ", &entries, &service), None);
+        assert_eq!(
+            scoped_hint("cargo ", &entries, &plain).as_deref(),
+            Some("fmt")
+        );
+        assert_eq!(
+            scoped_hint("make", &entries, &plain).as_deref(),
+            Some(" deploy")
+        );
+        assert_eq!(scoped_hint("make deploy", &entries, &plain), None);
+
+        fs::remove_dir_all(&root).ok();
+    }
+}
@@ -1,59 +1,703 @@
 use std::{
+    collections::HashSet,
+    env,
     fs::{File, OpenOptions},
-    io::{BufRead, BufReader, Write},
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    os::fd::{AsRawFd, RawFd},
     path::Path,
-    sync::{Mutex, atomic::AtomicUsize},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use lazy_static::lazy_static;
+use rustyline::history::History;
 
-use crate::{RL, Result};
+use crate::{RL, Result, directory::logical_cwd, utils::glob_match};
 
 lazy_static! {
-    pub static ref CURRENT_SESSION_HISTORY: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    pub static ref CURRENT_SESSION_HISTORY: Mutex<Vec<HistoryEntry>> = Mutex::new(Vec::new());
     pub static ref LAST_APPEND_INDEX: AtomicUsize = AtomicUsize::default();
+    // 已经从历史文件中读取的记录数，history -n 只读取之后的记录
+    pub static ref LAST_READ_INDEX: AtomicUsize = AtomicUsize::default();
+    // share 模式下已经合并或者写入过的记录，按时间戳和命令区分
+    pub static ref SYNCED_ENTRIES: Mutex<HashSet<(u64, String)>> = Mutex::new(HashSet::new());
+    // 当前执行的命令是否加入了历史，history -s 会用参数替换它
+    pub static ref CURRENT_LINE_ADDED: AtomicBool = AtomicBool::default();
+    // 和 RL 中的历史记录一一对应
+    pub static ref HISTORY_METADATA: Mutex<Vec<EntryMetadata>> = Mutex::new(Vec::new());
 }
 
-pub fn load_history<P: AsRef<Path>>(file: P) -> Result<()> {
-    let fp = BufReader::new(File::open(file)?);
+// 历史记录的附加信息，旧格式的历史文件中没有这些信息
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub timestamp: Option<u64>,
+    pub duration: Option<Duration>,
+    pub exit_code: Option<i32>,
+    pub cwd: Option<String>,
+}
+
+impl EntryMetadata {
+    pub fn now() -> Self {
+        EntryMetadata {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|time| time.as_secs()),
+            cwd: Some(logical_cwd().display().to_string()),
+            ..Default::default()
+        }
+    }
+
+    // 和 bash 兼容的 #<epoch> 注释行，后面依次是耗时 (毫秒)，退出码和工作目录
+    fn to_comment(&self) -> Option<String> {
+        let mut comment = format!("#{}", self.timestamp?);
+        if let (Some(duration), Some(exit_code)) = (self.duration, self.exit_code) {
+            comment += &format!(" {} {}", duration.as_millis(), exit_code);
+            if let Some(cwd) = &self.cwd {
+                comment += &format!(" {}", cwd);
+            }
+        }
+        Some(comment)
+    }
+
+    fn from_comment(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix('#')?;
+        if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let mut fields = rest.splitn(4, ' ');
+        let timestamp = fields.next()?.parse().ok()?;
+        Some(EntryMetadata {
+            timestamp: Some(timestamp),
+            duration: fields
+                .next()
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis),
+            exit_code: fields.next().and_then(|code| code.parse().ok()),
+            cwd: fields.next().map(|cwd| cwd.to_string()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub line: String,
+    pub metadata: EntryMetadata,
+}
+
+impl HistoryEntry {
+    fn to_file_lines(&self) -> String {
+        match self.metadata.to_comment() {
+            Some(comment) => format!("{}\n{}\n", comment, self.line),
+            None => format!("{}\n", self.line),
+        }
+    }
+}
+
+// flock 建议锁，多个会话同时读写历史文件时保证记录不会交错，drop 时释放
+struct FileLock(RawFd);
+
+impl FileLock {
+    fn new(file: &File, exclusive: bool) -> io::Result<Self> {
+        let operation = if exclusive {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_SH
+        };
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(FileLock(file.as_raw_fd()))
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::flock(self.0, libc::LOCK_UN);
+        }
+    }
+}
+
+pub fn read_history_file<P: AsRef<Path>>(file: P) -> Result<Vec<HistoryEntry>> {
+    let fp = File::open(file)?;
+    let _lock = FileLock::new(&fp, false)?;
+    parse_history(BufReader::new(&fp))
+}
+
+// 同时兼容只有命令的旧格式和带有 #<epoch> 注释行的格式
+fn parse_history<R: BufRead>(reader: R) -> Result<Vec<HistoryEntry>> {
+    let mut entries = vec![];
+    let mut metadata = None;
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(parsed) = EntryMetadata::from_comment(&line) {
+            metadata = Some(parsed);
+            continue;
+        }
+        entries.push(HistoryEntry {
+            line,
+            metadata: metadata.take().unwrap_or_default(),
+        });
+    }
+    Ok(entries)
+}
+
+fn format_history_entries(entries: &[HistoryEntry]) -> String {
+    entries.iter().map(HistoryEntry::to_file_lines).collect()
+}
+
+// HISTSIZE 和 HISTFILESIZE 未设置时的默认值，和 bash 一致
+const DEFAULT_HISTSIZE: usize = 500;
+
+// 解析 HISTSIZE 和 HISTFILESIZE，负数表示不限制，返回 None
+fn history_limit(value: Option<&str>, default: Option<usize>) -> Option<usize> {
+    match value.map(|value| value.trim().parse::<i64>()) {
+        Some(Ok(size)) if size < 0 => None,
+        Some(Ok(size)) => Some(size as usize),
+        _ => default,
+    }
+}
+
+pub fn history_size() -> Option<usize> {
+    history_limit(env::var("HISTSIZE").ok().as_deref(), Some(DEFAULT_HISTSIZE))
+}
+
+// 未设置 HISTFILESIZE 时使用 HISTSIZE 的值
+pub fn history_file_size() -> Option<usize> {
+    history_limit(env::var("HISTFILESIZE").ok().as_deref(), history_size())
+}
+
+// 历史文件的写入方式，exit 只在退出时追加，incremental 每条命令执行后追加，
+// share 在 incremental 的基础上每次显示提示符前合并其他会话的记录
+#[derive(Debug, PartialEq, Eq)]
+pub enum HistoryMode {
+    Exit,
+    Incremental,
+    Share,
+}
+
+impl HistoryMode {
+    pub fn current() -> Self {
+        match env::var("HISTORY_MODE").as_deref() {
+            Ok("incremental") => HistoryMode::Incremental,
+            Ok("share") => HistoryMode::Share,
+            _ => HistoryMode::Exit,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HistControl {
+    pub ignore_space: bool,
+    pub ignore_dups: bool,
+    pub erase_dups: bool,
+}
+
+impl HistControl {
+    // 未设置 HISTCONTROL 时保持以前的行为，忽略空格开头和连续重复的命令
+    pub fn parse(value: Option<&str>) -> Self {
+        let Some(value) = value else {
+            return HistControl {
+                ignore_space: true,
+                ignore_dups: true,
+                erase_dups: false,
+            };
+        };
+        let mut control = HistControl::default();
+        for item in value.split(':') {
+            match item {
+                "ignorespace" => control.ignore_space = true,
+                "ignoredups" => control.ignore_dups = true,
+                "ignoreboth" => {
+                    control.ignore_space = true;
+                    control.ignore_dups = true;
+                }
+                "erasedups" => control.erase_dups = true,
+                _ => {}
+            }
+        }
+        control
+    }
+
+    pub fn ignores(&self, line: &str, previous: Option<&str>) -> bool {
+        (self.ignore_space && line.starts_with(char::is_whitespace))
+            || (self.ignore_dups && previous == Some(line))
+    }
+}
+
+// HISTIGNORE 是冒号分隔的 glob 模式，需要匹配整行，& 表示上一条命令
+pub fn matches_histignore(patterns: &str, line: &str, previous: Option<&str>) -> bool {
+    patterns
+        .split(':')
+        .filter(|pattern| !pattern.is_empty())
+        .any(|pattern| {
+            if pattern == "&" {
+                previous == Some(line)
+            } else {
+                glob_match(pattern, line)
+            }
+        })
+}
+
+// 添加到 RL 中，并保持 HISTORY_METADATA 与之对齐，返回是否真正添加了
+pub fn add_history_entry(entry: HistoryEntry) -> bool {
     let mut rl = RL.lock().expect("Failed to require history");
-    for line in fp.lines() {
-        rl.add_history_entry(line?).ok();
+    let mut metadata = HISTORY_METADATA
+        .lock()
+        .expect("Failed to get history metadata");
+    rl.history_mut()
+        .set_max_len(history_size().unwrap_or(usize::MAX))
+        .ok();
+    let added = rl.add_history_entry(entry.line).unwrap_or(false);
+    if added {
+        metadata.push(entry.metadata);
+    }
+    // 超出 HISTSIZE 时最旧的记录会被丢弃
+    let excess = metadata.len().saturating_sub(rl.history().len());
+    metadata.drain(..excess);
+    added
+}
+
+// 记录用户输入的命令，按照 HISTCONTROL 和 HISTIGNORE 过滤，返回是否加入了历史
+pub fn remember_command(entry: HistoryEntry) -> bool {
+    let entries = history_entries();
+    let previous = entries.last().map(|entry| entry.line.as_str());
+    let control = HistControl::parse(env::var("HISTCONTROL").ok().as_deref());
+    let ignored = control.ignores(&entry.line, previous)
+        || env::var("HISTIGNORE")
+            .is_ok_and(|patterns| matches_histignore(&patterns, &entry.line, previous));
+    if ignored || entry.line.is_empty() {
+        return false;
+    }
+
+    let mut session = CURRENT_SESSION_HISTORY
+        .lock()
+        .expect("Failed to get current session history");
+    let mut append_index = LAST_APPEND_INDEX.load(Ordering::Relaxed);
+    // erasedups 会删除之前所有相同的命令
+    if control.erase_dups && entries.iter().any(|old| old.line == entry.line) {
+        replace_history(
+            entries
+                .into_iter()
+                .filter(|old| old.line != entry.line)
+                .collect(),
+        );
+        let erased_before_append = session[..append_index.min(session.len())]
+            .iter()
+            .filter(|old| old.line == entry.line)
+            .count();
+        append_index -= erased_before_append;
+        session.retain(|old| old.line != entry.line);
+    }
+
+    if !add_history_entry(entry.clone()) {
+        LAST_APPEND_INDEX.store(append_index, Ordering::Relaxed);
+        return false;
+    }
+    session.push(entry);
+    if let Some(size) = history_size() {
+        let excess = session.len().saturating_sub(size);
+        session.drain(..excess);
+        append_index = append_index.saturating_sub(excess);
+    }
+    LAST_APPEND_INDEX.store(append_index, Ordering::Relaxed);
+    true
+}
+
+// 命令执行结束后补充耗时和退出码
+pub fn finish_last_entry(duration: Duration, exit_code: i32) {
+    // 和 remember_command 一样按 SESSION -> RL -> METADATA 的顺序加锁，避免死锁
+    let mut session = CURRENT_SESSION_HISTORY
+        .lock()
+        .expect("Failed to get current session history");
+    if let Some(last) = session.last_mut() {
+        last.metadata.duration = Some(duration);
+        last.metadata.exit_code = Some(exit_code);
+    }
+    let mut metadata = HISTORY_METADATA
+        .lock()
+        .expect("Failed to get history metadata");
+    if let Some(last) = metadata.last_mut() {
+        last.duration = Some(duration);
+        last.exit_code = Some(exit_code);
+    }
+}
+
+// 从历史中去掉正在执行的这条命令，用于 history -s 和 fc 用别的命令替换它
+pub fn forget_current_line() {
+    if !CURRENT_LINE_ADDED.swap(false, Ordering::Relaxed) {
+        return;
+    }
+    let mut entries = history_entries();
+    entries.pop();
+    replace_history(entries);
+    CURRENT_SESSION_HISTORY
+        .lock()
+        .expect("Failed to get current session history")
+        .pop();
+}
+
+pub fn history_entries() -> Vec<HistoryEntry> {
+    let rl = RL.lock().expect("Failed to require history");
+    let metadata = HISTORY_METADATA
+        .lock()
+        .expect("Failed to get history metadata");
+    rl.history()
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| HistoryEntry {
+            line: line.clone(),
+            metadata: metadata.get(idx).cloned().unwrap_or_default(),
+        })
+        .collect()
+}
+
+// 用 entries 替换全部历史记录
+pub fn replace_history(entries: Vec<HistoryEntry>) {
+    let mut rl = RL.lock().expect("Failed to require history");
+    let mut metadata = HISTORY_METADATA
+        .lock()
+        .expect("Failed to get history metadata");
+    let history = rl.history_mut();
+    history.clear().ok();
+    metadata.clear();
+    for entry in entries {
+        if history.add_owned(entry.line).unwrap_or(false) {
+            metadata.push(entry.metadata);
+        }
+    }
+    let excess = metadata.len().saturating_sub(history.len());
+    metadata.drain(..excess);
+}
+
+// 删除 [start, end] 范围内的记录，本次会话中还没有追加到文件的记录也不再追加
+pub fn delete_history(start: usize, end: usize) {
+    let mut entries = history_entries();
+    let deleted: Vec<HistoryEntry> = entries.drain(start..=end).collect();
+    replace_history(entries);
+
+    let mut session = CURRENT_SESSION_HISTORY
+        .lock()
+        .expect("Failed to get current session history");
+    let append_index = remove_deleted_entries(
+        &mut session,
+        LAST_APPEND_INDEX.load(Ordering::Relaxed),
+        &deleted,
+    );
+    LAST_APPEND_INDEX.store(append_index, Ordering::Relaxed);
+}
+
+// 从本次会话的记录中去掉被删除的记录，返回调整后的追加位置
+fn remove_deleted_entries(
+    session: &mut Vec<HistoryEntry>,
+    mut append_index: usize,
+    deleted: &[HistoryEntry],
+) -> usize {
+    for entry in deleted.iter().rev() {
+        if let Some(pos) = session.iter().rposition(|old| old == entry) {
+            session.remove(pos);
+            if pos < append_index {
+                append_index -= 1;
+            }
+        }
+    }
+    append_index
+}
+
+// 返回读取的记录数
+pub fn load_history<P: AsRef<Path>>(file: P) -> Result<usize> {
+    let entries = read_history_file(file)?;
+    let count = entries.len();
+    unsynced_entries(
+        &entries,
+        &mut SYNCED_ENTRIES
+            .lock()
+            .expect("Failed to get synced history entries"),
+    );
+    for entry in entries {
+        add_history_entry(entry);
+    }
+    Ok(count)
+}
+
+// 只读取上次读取之后新增的记录
+pub fn load_new_history<P: AsRef<Path>>(file: P) -> Result<()> {
+    let entries = read_history_file(file)?;
+    let count = entries.len();
+    let start = LAST_READ_INDEX.load(Ordering::Relaxed).min(count);
+    for entry in entries.into_iter().skip(start) {
+        add_history_entry(entry);
+    }
+    LAST_READ_INDEX.store(count, Ordering::Relaxed);
+    Ok(())
+}
+
+// 返回还没有同步过的记录，并把它们记为已同步，没有时间戳的记录无法区分，直接忽略
+fn unsynced_entries(
+    entries: &[HistoryEntry],
+    synced: &mut HashSet<(u64, String)>,
+) -> Vec<HistoryEntry> {
+    entries
+        .iter()
+        .filter(|entry| {
+            entry
+                .metadata
+                .timestamp
+                .is_some_and(|timestamp| synced.insert((timestamp, entry.line.clone())))
+        })
+        .cloned()
+        .collect()
+}
+
+// 合并其他会话写入的记录。文件会被 HISTFILESIZE 截断，所以不能按照位置判断哪些是新记录；
+// 时间戳是命令开始的时间，执行时间长的命令写入得晚，所以也不能按照时间戳判断
+pub fn merge_shared_history<P: AsRef<Path>>(file: P) -> Result<()> {
+    let entries = read_history_file(file)?;
+    let mut synced = SYNCED_ENTRIES
+        .lock()
+        .expect("Failed to get synced history entries");
+    // history -r 等方式读取的记录已经在历史中了
+    unsynced_entries(&history_entries(), &mut synced);
+    for entry in unsynced_entries(&entries, &mut synced) {
+        add_history_entry(entry);
     }
     Ok(())
 }
 
-pub fn save_history<P: AsRef<Path>>(file: P, is_append: bool) -> Result<()> {
+// 在排他锁中写入历史文件，写入之后按 HISTFILESIZE 截断
+fn write_history_file<P: AsRef<Path>, T>(
+    file: P,
+    write: impl FnOnce(&mut File) -> Result<T>,
+) -> Result<T> {
     let mut fp = OpenOptions::new()
         .read(true)
-        .write(true)
-        .append(is_append)
+        .append(true)
         .create(true)
         .open(file)?;
-    let rl = RL.lock().expect("Failed to require history");
-    let hists_to_save = if is_append {
-        let mut hist_iter = rl.history().iter();
-        let mut fp_iter = BufReader::new(&fp).lines();
+    let _lock = FileLock::new(&fp, true)?;
+    let result = write(&mut fp)?;
+    if let Some(size) = history_file_size() {
+        fp.seek(SeekFrom::Start(0))?;
+        let entries = parse_history(BufReader::new(&fp))?;
+        if entries.len() > size {
+            fp.set_len(0)?;
+            fp.write_all(format_history_entries(&entries[entries.len() - size..]).as_bytes())?;
+        }
+    }
+    Ok(result)
+}
 
-        let mut hist = hist_iter.next();
-        let mut line = fp_iter.next();
+// 覆盖写入全部历史记录
+pub fn save_history<P: AsRef<Path>>(file: P) -> Result<()> {
+    let entries = history_entries();
+    write_history_file(file, |fp| {
+        fp.set_len(0)?;
+        fp.write_all(format_history_entries(&entries).as_bytes())?;
+        Ok(())
+    })?;
+    LAST_APPEND_INDEX.store(
+        CURRENT_SESSION_HISTORY
+            .lock()
+            .expect("Failed to get current session history")
+            .len(),
+        Ordering::Relaxed,
+    );
+    Ok(())
+}
 
-        while hist.is_some() && line.is_some() {
-            if *hist.unwrap() != line.unwrap()? {
-                break;
-            }
-            hist = hist_iter.next();
-            line = fp_iter.next();
-        }
-        hist_iter.fold(
-            hist.map_or(String::new(), |s| s.to_string() + "\n"),
-            |acc, hist| acc + hist + "\n",
+// 追加本次会话中还没有写入的记录，返回追加的记录数
+pub fn append_session_history<P: AsRef<Path>>(file: P) -> Result<usize> {
+    let session = CURRENT_SESSION_HISTORY
+        .lock()
+        .expect("Failed to get current session history");
+    let start = LAST_APPEND_INDEX.load(Ordering::Relaxed).min(session.len());
+    if start == session.len() {
+        return Ok(0);
+    }
+    write_history_file(file, |fp| {
+        fp.write_all(format_history_entries(&session[start..]).as_bytes())?;
+        Ok(())
+    })?;
+    // 之后被 history -d 删除的记录也不会再从文件中合并回来
+    unsynced_entries(
+        &session[start..],
+        &mut SYNCED_ENTRIES
+            .lock()
+            .expect("Failed to get synced history entries"),
+    );
+    LAST_APPEND_INDEX.store(session.len(), Ordering::Relaxed);
+    Ok(session.len() - start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_metadata_comment() {
+        let metadata = EntryMetadata {
+            timestamp: Some(1700000000),
+            duration: Some(Duration::from_millis(1500)),
+            exit_code: Some(1),
+            cwd: Some("/tmp/a b".to_string()),
+        };
+        let comment = metadata.to_comment().unwrap();
+        assert_eq!(comment, "#1700000000 1500 1 /tmp/a b");
+        assert_eq!(EntryMetadata::from_comment(&comment), Some(metadata));
+
+        // bash 写入的只有时间戳
+        assert_eq!(
+            EntryMetadata::from_comment("#1700000000"),
+            Some(EntryMetadata {
+                timestamp: Some(1700000000),
+                ..Default::default()
+            })
+        );
+        assert_eq!(EntryMetadata::from_comment("# comment"), None);
+        assert_eq!(EntryMetadata::default().to_comment(), None);
+    }
+
+    #[test]
+    fn test_read_history_file() {
+        let file = std::env::temp_dir().join(format!("history-test-{}", std::process::id()));
+        std::fs::write(
+            &file,
+            "ls\n#1700000000 20 0 /tmp\necho a\n#1700000001\npwd\n",
         )
-    } else {
-        rl.history()
-            .iter()
-            .fold(String::new(), |acc, hist| acc + hist + "\n")
-    };
-    fp.write_all(hists_to_save.as_bytes())?;
-    Ok(())
+        .unwrap();
+        let entries = read_history_file(&file).unwrap();
+        std::fs::remove_file(&file).ok();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].line, "ls");
+        assert_eq!(entries[0].metadata, EntryMetadata::default());
+        assert_eq!(entries[1].metadata.exit_code, Some(0));
+        assert_eq!(entries[1].metadata.cwd.as_deref(), Some("/tmp"));
+        assert_eq!(entries[2].metadata.timestamp, Some(1700000001));
+        assert_eq!(
+            format_history_entries(&entries),
+            "ls\n#1700000000 20 0 /tmp\necho a\n#1700000001\npwd\n"
+        );
+    }
+
+    #[test]
+    fn test_write_history_file() {
+        let file = std::env::temp_dir().join(format!("history-write-test-{}", std::process::id()));
+        std::fs::write(&file, "ls\n").unwrap();
+        write_history_file(&file, |fp| {
+            // 已经持有排他锁，其他文件描述符无法再获取锁
+            let other = File::open(&file)?;
+            let result = unsafe { libc::flock(other.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) };
+            assert_eq!(result, -1);
+            fp.write_all(b"#1700000000\npwd\n")?;
+            Ok(())
+        })
+        .unwrap();
+        let entries = read_history_file(&file).unwrap();
+        std::fs::remove_file(&file).ok();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].line, "pwd");
+        assert_eq!(entries[1].metadata.timestamp, Some(1700000000));
+    }
+
+    #[test]
+    fn test_remove_deleted_entries() {
+        let entry = |line: &str, timestamp| HistoryEntry {
+            line: line.to_string(),
+            metadata: EntryMetadata {
+                timestamp: Some(timestamp),
+                ..Default::default()
+            },
+        };
+        let mut session = vec![entry("a", 2), entry("b", 3), entry("c", 4)];
+        // a 已经追加到文件中，删除 a 和 b 之后只剩 c 需要追加
+        let append_index = remove_deleted_entries(&mut session, 1, &[entry("a", 2), entry("b", 3)]);
+        assert_eq!(session, vec![entry("c", 4)]);
+        assert_eq!(append_index, 0);
+
+        // 不属于本次会话的记录不影响追加位置
+        let mut session = vec![entry("a", 2), entry("b", 3)];
+        let append_index =
+            remove_deleted_entries(&mut session, 1, &[entry("ls", 1), entry("b", 3)]);
+        assert_eq!(session, vec![entry("a", 2)]);
+        assert_eq!(append_index, 1);
+    }
+
+    #[test]
+    fn test_unsynced_entries() {
+        let entry = |line: &str, timestamp| HistoryEntry {
+            line: line.to_string(),
+            metadata: EntryMetadata {
+                timestamp: Some(timestamp),
+                ..Default::default()
+            },
+        };
+        let mut synced = HashSet::new();
+        assert_eq!(
+            unsynced_entries(&[entry("ls", 1), entry("pwd", 5)], &mut synced),
+            vec![entry("ls", 1), entry("pwd", 5)]
+        );
+        // 其他会话中执行时间长的命令写入得晚，时间戳比已经合并的记录早
+        let entries = [
+            entry("ls", 1),
+            entry("pwd", 5),
+            entry("sleep 10", 3),
+            HistoryEntry {
+                line: "echo".to_string(),
+                metadata: EntryMetadata::default(),
+            },
+        ];
+        assert_eq!(
+            unsynced_entries(&entries, &mut synced),
+            vec![entry("sleep 10", 3)]
+        );
+        assert_eq!(unsynced_entries(&entries, &mut synced), vec![]);
+    }
+
+    #[test]
+    fn test_history_limit() {
+        assert_eq!(history_limit(None, Some(500)), Some(500));
+        assert_eq!(history_limit(Some("100"), Some(500)), Some(100));
+        assert_eq!(history_limit(Some("0"), Some(500)), Some(0));
+        assert_eq!(history_limit(Some("-1"), Some(500)), None);
+        assert_eq!(history_limit(Some("abc"), None), None);
+    }
+
+    #[test]
+    fn test_hist_control() {
+        let control = HistControl::parse(Some("ignorespace:erasedups"));
+        assert_eq!(
+            control,
+            HistControl {
+                ignore_space: true,
+                ignore_dups: false,
+                erase_dups: true,
+            }
+        );
+        assert!(control.ignores(" secret", None));
+        assert!(!control.ignores("ls", Some("ls")));
+
+        let control = HistControl::parse(Some("ignoreboth"));
+        assert!(control.ignores("ls", Some("ls")));
+        assert!(!control.ignores("ls", Some("pwd")));
+        assert!(!HistControl::parse(Some("")).ignores(" ls", Some(" ls")));
+        assert!(HistControl::parse(None).ignores(" ls", None));
+    }
+
+    #[test]
+    fn test_matches_histignore() {
+        let patterns = "ls:[bf]g:exit:history*:&";
+        assert!(matches_histignore(patterns, "ls", None));
+        assert!(!matches_histignore(patterns, "ls -l", None));
+        assert!(matches_histignore(patterns, "fg", None));
+        assert!(matches_histignore(patterns, "history -c", None));
+        assert!(matches_histignore(patterns, "make", Some("make")));
+        assert!(!matches_histignore(patterns, "make", Some("ls")));
+        assert!(!matches_histignore("", "ls", None));
+    }
 }
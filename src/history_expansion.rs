@@ -0,0 +1,457 @@
+use crate::Result;
+
+// 历史展开的结果，print_only 对应 :p 修饰符，只打印不执行
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExpandedLine {
+    pub line: String,
+    pub print_only: bool,
+}
+
+// 展开 !!，!n，!-n，!prefix，!?str?，!$ 等历史引用，以及行首的 ^old^new^
+// history 从旧到新排列，没有需要展开的内容时返回 None
+pub fn expand_history(line: &str, history: &[String]) -> Result<Option<ExpandedLine>> {
+    // ^old^new^ 等价于 !!:s^old^new^
+    let source: Vec<char> = if line.starts_with('^') {
+        format!("!!:s{}", line).chars().collect()
+    } else {
+        line.chars().collect()
+    };
+
+    let mut expanded = String::new();
+    let mut changed = false;
+    let mut print_only = false;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut pos = 0;
+    while pos < source.len() {
+        let c = source[pos];
+        match c {
+            '\\' if !in_single_quote => {
+                expanded.push(c);
+                if let Some(&next) = source.get(pos + 1) {
+                    expanded.push(next);
+                }
+                pos += 2;
+                continue;
+            }
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            '!' if !in_single_quote && is_history_reference(&source, pos) => {
+                let mut reference = Reference {
+                    source: &source,
+                    pos: pos + 1,
+                    history,
+                };
+                let (text, print) = reference.expand(&expanded)?;
+                expanded.push_str(&text);
+                print_only |= print;
+                changed = true;
+                pos = reference.pos;
+                continue;
+            }
+            _ => {}
+        }
+        expanded.push(c);
+        pos += 1;
+    }
+
+    Ok(changed.then_some(ExpandedLine {
+        line: expanded,
+        print_only,
+    }))
+}
+
+// ! 后面是空白，=，( 或者行尾时不展开，比如 [[ ! -f a ]] 和 a != b
+fn is_history_reference(source: &[char], pos: usize) -> bool {
+    match source.get(pos + 1) {
+        None => false,
+        Some(c) if c.is_whitespace() || *c == '=' || *c == '(' => false,
+        // "!" 中的 ! 不展开
+        Some('"') => false,
+        Some(_) => true,
+    }
+}
+
+// 按空白拆分历史记录中的单词，引号中的内容不拆分，操作符单独作为一个单词
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut quote = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if c == q => {
+                quote = None;
+                word.push(c);
+            }
+            (Some(_), _) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.push(c);
+            }
+            (None, '\\') => {
+                word.push(c);
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+            }
+            (None, _) if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            (None, ';' | '&' | '|' | '<' | '>') => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                let mut operator = c.to_string();
+                while let Some(&next) = chars.peek()
+                    && next == c
+                {
+                    operator.push(next);
+                    chars.next();
+                }
+                words.push(operator);
+            }
+            (None, _) => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+struct Reference<'a> {
+    source: &'a [char],
+    pos: usize,
+    history: &'a [String],
+}
+
+impl Reference<'_> {
+    fn peek(&self) -> Option<char> {
+        self.source.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.source.get(self.pos + offset).copied()
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&pred) {
+            self.pos += 1;
+        }
+        self.source[start..self.pos].iter().collect()
+    }
+
+    // 返回展开后的文本，以及是否带有 :p
+    fn expand(&mut self, line_so_far: &str) -> Result<(String, bool)> {
+        let event = self.event(line_so_far)?;
+        let words = split_words(&event);
+        let mut text = match self.word_designator(&words) {
+            Some(result) => result.map_err(|spec| format!("{}: bad word specifier", spec))?,
+            None => event,
+        };
+
+        let mut print_only = false;
+        while self.peek() == Some(':') {
+            let Some(modifier) = self.peek_at(1) else {
+                break;
+            };
+            match modifier {
+                'h' | 't' | 'r' | 'e' | 'p' | 'q' => {
+                    self.pos += 2;
+                    text = match modifier {
+                        'h' => text.rsplit_once('/').map_or(text.clone(), |(head, _)| {
+                            if head.is_empty() { "/" } else { head }.to_string()
+                        }),
+                        't' => text
+                            .rsplit_once('/')
+                            .map_or(text.clone(), |(_, tail)| tail.to_string()),
+                        'r' => match text.rfind('.') {
+                            Some(idx) if !text[idx..].contains('/') => text[..idx].to_string(),
+                            _ => text,
+                        },
+                        'e' => match text.rfind('.') {
+                            Some(idx) if !text[idx..].contains('/') => text[idx..].to_string(),
+                            _ => text,
+                        },
+                        'q' => format!("'{}'", text.replace('\'', r"'\''")),
+                        _ => {
+                            print_only = true;
+                            text
+                        }
+                    };
+                }
+                's' => {
+                    self.pos += 2;
+                    text = self.substitute(&text, false)?;
+                }
+                'g' if self.peek_at(2) == Some('s') => {
+                    self.pos += 3;
+                    text = self.substitute(&text, true)?;
+                }
+                _ => break,
+            }
+        }
+
+        Ok((text, print_only))
+    }
+
+    // 事件指示符，!$ 这类省略事件的写法指向上一条命令
+    fn event(&mut self, line_so_far: &str) -> Result<String> {
+        let spec_start = self.pos;
+        let index = match self.peek() {
+            Some('!') => {
+                self.pos += 1;
+                self.history.len().checked_sub(1)
+            }
+            Some('#') => {
+                self.pos += 1;
+                return Ok(line_so_far.to_string());
+            }
+            Some('^' | '$' | '*' | '%' | ':') => self.history.len().checked_sub(1),
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let negative = c == '-';
+                if negative {
+                    self.pos += 1;
+                }
+                let num = self.take_while(|c| c.is_ascii_digit());
+                let num: usize = num.parse().unwrap_or(0);
+                if negative {
+                    self.history.len().checked_sub(num)
+                } else {
+                    num.checked_sub(1)
+                }
+            }
+            Some('?') => {
+                self.pos += 1;
+                let pattern = self.take_while(|c| c != '?');
+                if self.peek() == Some('?') {
+                    self.pos += 1;
+                }
+                self.history
+                    .iter()
+                    .rposition(|hist| hist.contains(&pattern))
+            }
+            _ => {
+                let prefix = self.take_while(|c| !c.is_whitespace() && !":;&|<>'\"".contains(c));
+                if prefix.is_empty() {
+                    None
+                } else {
+                    self.history
+                        .iter()
+                        .rposition(|hist| hist.starts_with(&prefix))
+                }
+            }
+        };
+        let spec: String = self.source[spec_start..self.pos].iter().collect();
+        index
+            .and_then(|idx| self.history.get(idx))
+            .cloned()
+            .ok_or_else(|| format!("!{}: event not found", spec).into())
+    }
+
+    // :0，:n，:^，:$，:*，:n-m，:n*，:n-，返回 None 表示没有单词指示符
+    fn word_designator(&mut self, words: &[String]) -> Option<std::result::Result<String, String>> {
+        let spec_start = self.pos;
+        match (self.peek(), self.peek_at(1)) {
+            (Some('^' | '$' | '*' | '%'), _) => {}
+            (Some(':'), Some(c)) if c.is_ascii_digit() || "^$*-%".contains(c) => self.pos += 1,
+            _ => return None,
+        }
+
+        let last = words.len().saturating_sub(1);
+        let parse_index = |this: &mut Self| -> Option<usize> {
+            match this.peek() {
+                Some('^') => {
+                    this.pos += 1;
+                    Some(1)
+                }
+                Some('$') => {
+                    this.pos += 1;
+                    Some(last)
+                }
+                Some(c) if c.is_ascii_digit() => {
+                    this.take_while(|c| c.is_ascii_digit()).parse().ok()
+                }
+                _ => None,
+            }
+        };
+
+        let range = if self.peek() == Some('*') {
+            self.pos += 1;
+            Some((1, last))
+        } else if self.peek() == Some('%') {
+            // 不支持 !?str?% 引用匹配的单词
+            self.pos += 1;
+            None
+        } else {
+            let first = if self.peek() == Some('-') {
+                Some(0)
+            } else {
+                parse_index(self)
+            };
+            match (first, self.peek()) {
+                (Some(first), Some('*')) => {
+                    self.pos += 1;
+                    Some((first, last))
+                }
+                (Some(first), Some('-')) => {
+                    self.pos += 1;
+                    match parse_index(self) {
+                        Some(end) => Some((first, end)),
+                        None => Some((first, last.saturating_sub(1))),
+                    }
+                }
+                (Some(first), _) => Some((first, first)),
+                (None, _) => None,
+            }
+        };
+
+        let spec: String = self.source[spec_start..self.pos].iter().collect();
+        let result = match range {
+            // !:* 在只有命令名时展开为空
+            Some((1, end)) if words.len() == 1 && end == 0 => Ok(String::new()),
+            Some((first, end)) if first <= end && end < words.len() => {
+                Ok(words[first..=end].join(" "))
+            }
+            _ => Err(spec),
+        };
+        Some(result)
+    }
+
+    // s/old/new/，分隔符可以是任意字符，new 中的 & 表示 old
+    fn substitute(&mut self, text: &str, global: bool) -> Result<String> {
+        let start = self.pos;
+        let Some(delimiter) = self.peek() else {
+            return Err(":s: bad substitution".into());
+        };
+        self.pos += 1;
+        let old = self.substitution_part(delimiter, None);
+        let new = self.substitution_part(delimiter, Some(&old));
+        let spec: String = self.source[start - 1..self.pos].iter().collect();
+        if old.is_empty() || !text.contains(&old) {
+            return Err(format!(":{}: substitution failed", spec).into());
+        }
+        Ok(if global {
+            text.replace(&old, &new)
+        } else {
+            text.replacen(&old, &new, 1)
+        })
+    }
+
+    // 读取到分隔符或者行尾，\ 可以转义分隔符和 &
+    fn substitution_part(&mut self, delimiter: char, old: Option<&str>) -> String {
+        let mut part = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                _ if c == delimiter => break,
+                '\\' if self
+                    .peek()
+                    .is_some_and(|next| next == delimiter || next == '&') =>
+                {
+                    part.push(self.peek().unwrap());
+                    self.pos += 1;
+                }
+                '&' if old.is_some() => part.push_str(old.unwrap()),
+                _ => part.push(c),
+            }
+        }
+        part
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::vec_str_to_vec_string;
+
+    use super::*;
+
+    fn expand(line: &str) -> Option<String> {
+        let history: Vec<String> = vec_str_to_vec_string(&[
+            "ls -l /usr/local/lib/file.tar.gz",
+            "echo 'a b' c",
+            "cat foo.txt | grep bar",
+        ]);
+        expand_history(line, &history)
+            .unwrap()
+            .map(|expanded| expanded.line)
+    }
+
+    fn expand_err(line: &str) -> String {
+        let history: Vec<String> = vec_str_to_vec_string(&["echo a"]);
+        expand_history(line, &history).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_event_designators() {
+        assert_eq!(expand("echo hi"), None);
+        assert_eq!(
+            expand("sudo !!"),
+            Some("sudo cat foo.txt | grep bar".into())
+        );
+        assert_eq!(
+            expand("!1"),
+            Some("ls -l /usr/local/lib/file.tar.gz".into())
+        );
+        assert_eq!(expand("!-2"), Some("echo 'a b' c".into()));
+        assert_eq!(expand("!ec"), Some("echo 'a b' c".into()));
+        assert_eq!(expand("!?foo?"), Some("cat foo.txt | grep bar".into()));
+        assert_eq!(expand("echo x !#"), Some("echo x echo x ".into()));
+        assert_eq!(expand("[[ ! -f a ]] && [ a != b ]"), None);
+        assert_eq!(expand("echo '!!' \\!! !"), None);
+        assert_eq!(
+            expand("echo \"!!\""),
+            Some("echo \"cat foo.txt | grep bar\"".into())
+        );
+        assert_eq!(expand_err("!x"), "!x: event not found");
+        assert_eq!(expand_err("!5"), "!5: event not found");
+    }
+
+    #[test]
+    fn test_word_designators() {
+        assert_eq!(expand("vi !$"), Some("vi bar".into()));
+        assert_eq!(expand("!!:0"), Some("cat".into()));
+        assert_eq!(expand("echo !^ !-2:*"), Some("echo foo.txt 'a b' c".into()));
+        assert_eq!(expand("echo !!:1-3"), Some("echo foo.txt | grep".into()));
+        assert_eq!(expand("echo !!:2*"), Some("echo | grep bar".into()));
+        assert_eq!(expand("echo !!:2-"), Some("echo | grep".into()));
+        assert_eq!(expand("echo !!:-1"), Some("echo cat foo.txt".into()));
+        assert_eq!(expand_err("echo !!:5"), ":5: bad word specifier");
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(expand("cd !1:$:h"), Some("cd /usr/local/lib".into()));
+        assert_eq!(expand("echo !1:$:t"), Some("echo file.tar.gz".into()));
+        assert_eq!(
+            expand("echo !1:$:r"),
+            Some("echo /usr/local/lib/file.tar".into())
+        );
+        assert_eq!(expand("echo !1:$:e"), Some("echo .gz".into()));
+        assert_eq!(expand("echo !1:$:t:r:r"), Some("echo file".into()));
+        assert_eq!(
+            expand("!!:s/foo/&.bak/"),
+            Some("cat foo.bak.txt | grep bar".into())
+        );
+        assert_eq!(
+            expand("!1:gs/l/L"),
+            Some("Ls -L /usr/LocaL/Lib/fiLe.tar.gz".into())
+        );
+        assert_eq!(expand("^foo^bar"), Some("cat bar.txt | grep bar".into()));
+        assert_eq!(expand("!!:0:q"), Some("'cat'".into()));
+
+        let history = vec_str_to_vec_string::<Vec<_>>(&["echo a"]);
+        assert_eq!(
+            expand_history("!!:p", &history).unwrap(),
+            Some(ExpandedLine {
+                line: "echo a".into(),
+                print_only: true,
+            })
+        );
+        assert_eq!(expand_err("^x^y"), ":s^x^y: substitution failed");
+    }
+}
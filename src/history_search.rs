@@ -0,0 +1,471 @@
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    io::{self, IsTerminal, Write},
+    sync::Mutex,
+};
+
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use lazy_static::lazy_static;
+use regex::Regex;
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, Movement, RepeatCount};
+
+use crate::{directory::logical_cwd, history::HistoryEntry};
+
+// 同时预览的匹配数量
+const MAX_PREVIEW: usize = 8;
+
+lazy_static! {
+    // Ctrl-R 和提示都在 readline 中触发，此时 RL 已经被锁住，所以在显示提示符前保存一份历史记录
+    pub static ref SEARCH_HISTORY: Mutex<Vec<HistoryEntry>> = Mutex::new(Vec::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchMode {
+    Substring,
+    Fuzzy,
+    Regex,
+}
+
+impl SearchMode {
+    fn next(self) -> Self {
+        match self {
+            SearchMode::Substring => SearchMode::Fuzzy,
+            SearchMode::Fuzzy => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Substring,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SearchMode::Substring => "substring",
+            SearchMode::Fuzzy => "fuzzy",
+            SearchMode::Regex => "regex",
+        }
+    }
+}
+
+// 按退出码过滤，没有记录退出码的命令只在 Any 时显示
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitFilter {
+    Any,
+    Success,
+    Failure,
+}
+
+impl ExitFilter {
+    fn next(self) -> Self {
+        match self {
+            ExitFilter::Any => ExitFilter::Success,
+            ExitFilter::Success => ExitFilter::Failure,
+            ExitFilter::Failure => ExitFilter::Any,
+        }
+    }
+
+    fn accepts(self, exit_code: Option<i32>) -> bool {
+        match self {
+            ExitFilter::Any => true,
+            ExitFilter::Success => exit_code == Some(0),
+            ExitFilter::Failure => exit_code.is_some_and(|code| code != 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistorySearch {
+    pub query: String,
+    pub mode: SearchMode,
+    pub cwd_only: bool,
+    pub exit_filter: ExitFilter,
+    pub selected: usize,
+}
+
+impl HistorySearch {
+    pub fn new(query: &str) -> Self {
+        HistorySearch {
+            query: query.to_string(),
+            mode: SearchMode::Substring,
+            cwd_only: false,
+            exit_filter: ExitFilter::Any,
+            selected: 0,
+        }
+    }
+
+    // 返回去重后的匹配，最近的在前；fuzzy 模式按分数排序
+    pub fn search(&self, entries: &[HistoryEntry], cwd: &str) -> Result<Vec<String>, String> {
+        let regex = match self.mode {
+            SearchMode::Regex => Some(Regex::new(&self.query).map_err(|_| "invalid regex")?),
+            _ => None,
+        };
+        // 查询中没有大写字母时忽略大小写
+        let ignore_case = !self.query.chars().any(char::is_uppercase);
+        let query = if ignore_case {
+            self.query.to_lowercase()
+        } else {
+            self.query.clone()
+        };
+        let matcher = SkimMatcherV2::default();
+
+        let mut seen = HashSet::new();
+        let mut matches = vec![];
+        for entry in entries.iter().rev() {
+            if self.cwd_only && entry.metadata.cwd.as_deref() != Some(cwd) {
+                continue;
+            }
+            if !self.exit_filter.accepts(entry.metadata.exit_code) {
+                continue;
+            }
+            let line = &entry.line;
+            let score = match self.mode {
+                SearchMode::Substring if ignore_case => {
+                    line.to_lowercase().contains(&query).then_some(0)
+                }
+                SearchMode::Substring => line.contains(&query).then_some(0),
+                SearchMode::Fuzzy if query.is_empty() => Some(0),
+                SearchMode::Fuzzy => matcher.fuzzy_match(line, &self.query),
+                SearchMode::Regex => regex.as_ref().unwrap().is_match(line).then_some(0),
+            };
+            if let Some(score) = score
+                && seen.insert(line.as_str())
+            {
+                matches.push((score, line.clone()));
+            }
+        }
+        // sort_by_key 是稳定排序，分数相同时保持最近的在前
+        matches.sort_by_key(|(score, _)| Reverse(*score));
+        Ok(matches.into_iter().map(|(_, line)| line).collect())
+    }
+
+    fn header(&self) -> String {
+        let mut flags = vec![self.mode.label()];
+        if self.cwd_only {
+            flags.push("cwd");
+        }
+        match self.exit_filter {
+            ExitFilter::Any => {}
+            ExitFilter::Success => flags.push("success"),
+            ExitFilter::Failure => flags.push("failure"),
+        }
+        format!("search ({}): ", flags.join(", "))
+    }
+
+    // 第一行是查询，之后是预览的匹配，最后一行是按键提示
+    pub fn render(&self, matches: &Result<Vec<String>, String>, width: usize) -> Vec<String> {
+        let truncate = |line: &str| {
+            line.chars()
+                .take(width.saturating_sub(3))
+                .collect::<String>()
+        };
+        let mut lines = vec![truncate(&format!("{}{}", self.header(), self.query))];
+        match matches {
+            Err(err) => lines.push(format!("  \x1b[31m{}\x1b[0m", err)),
+            Ok(matches) if matches.is_empty() => lines.push("  (no matches)".to_string()),
+            Ok(matches) => {
+                let start = self.selected.saturating_sub(MAX_PREVIEW - 1);
+                for (idx, line) in matches.iter().enumerate().skip(start).take(MAX_PREVIEW) {
+                    let line = truncate(line).replace(['\n', '\r'], " ");
+                    if idx == self.selected {
+                        lines.push(format!("\x1b[7m> {}\x1b[0m", line));
+                    } else {
+                        lines.push(format!("  {}", line));
+                    }
+                }
+            }
+        }
+        lines.push(format!(
+            "\x1b[2m{}\x1b[0m",
+            truncate("^R/^S move  ^T mode  ^O cwd  ^E exit status  Enter edit  Esc cancel")
+        ));
+        lines
+    }
+}
+
+enum Key {
+    Char(char),
+    Backspace,
+    ClearQuery,
+    Older,
+    Newer,
+    Accept,
+    Cancel,
+    ToggleMode,
+    ToggleCwd,
+    ToggleExit,
+    Other,
+}
+
+fn read_byte(timeout_ms: i32) -> Option<u8> {
+    let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    if unsafe { libc::poll(&mut fds, 1, timeout_ms) } <= 0 {
+        return None;
+    }
+    let mut byte = 0u8;
+    let len = unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+    (len == 1).then_some(byte)
+}
+
+// 终端处于 raw 模式，直接从标准输入读取按键
+fn read_key() -> Option<Key> {
+    let byte = read_byte(-1)?;
+    let key = match byte {
+        0x12 | 0x10 => Key::Older,
+        0x13 | 0x0e => Key::Newer,
+        b'\r' | b'\n' | b'\t' => Key::Accept,
+        0x03 | 0x07 => Key::Cancel,
+        0x7f | 0x08 => Key::Backspace,
+        0x15 => Key::ClearQuery,
+        0x14 => Key::ToggleMode,
+        0x0f => Key::ToggleCwd,
+        0x05 => Key::ToggleExit,
+        // 单独的 Esc 表示取消，否则是方向键等转义序列
+        0x1b => match read_byte(50) {
+            None => Key::Cancel,
+            Some(b'[' | b'O') => {
+                // 读到结束字符为止，超时按 ~ 处理
+                let mut last = read_byte(50).unwrap_or(b'~');
+                while !(0x40..=0x7e).contains(&last) {
+                    last = read_byte(50).unwrap_or(b'~');
+                }
+                match last {
+                    b'A' => Key::Older,
+                    b'B' => Key::Newer,
+                    _ => Key::Other,
+                }
+            }
+            Some(_) => Key::Other,
+        },
+        byte if byte < 0x20 => Key::Other,
+        byte => {
+            // UTF-8 多字节字符
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                bytes.push(read_byte(50)?);
+            }
+            match String::from_utf8(bytes).ok()?.chars().next() {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
+        }
+    };
+    Some(key)
+}
+
+fn terminal_width() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+        && size.ws_col > 0
+    {
+        size.ws_col as usize
+    } else {
+        80
+    }
+}
+
+// 在输入行下方绘制，光标停在查询行，只使用相对移动，屏幕滚动时也不会错位
+fn draw(out: &mut impl Write, lines: &[String], cursor_col: usize) -> io::Result<()> {
+    let mut buffer = String::from("\r");
+    buffer.push_str(
+        &lines
+            .iter()
+            .map(|line| format!("\x1b[K{}", line))
+            .collect::<Vec<_>>()
+            .join("\r\n"),
+    );
+    buffer.push_str("\x1b[J");
+    if lines.len() > 1 {
+        buffer.push_str(&format!("\x1b[{}A", lines.len() - 1));
+    }
+    buffer.push_str(&format!("\r\x1b[{}C", cursor_col));
+    out.write_all(buffer.as_bytes())?;
+    out.flush()
+}
+
+// 返回选中的命令，取消时返回 None
+fn run_search(search: &mut HistorySearch, entries: &[HistoryEntry]) -> io::Result<Option<String>> {
+    let cwd = logical_cwd().display().to_string();
+    let mut out = io::stdout();
+    // 移动到输入行的下一行
+    out.write_all(b"\r\n")?;
+    let result = loop {
+        let matches = search.search(entries, &cwd);
+        let count = matches.as_ref().map_or(0, Vec::len);
+        search.selected = search.selected.min(count.saturating_sub(1));
+        let width = terminal_width();
+        let lines = search.render(&matches, width);
+        let cursor_col = (search.header().chars().count() + search.query.chars().count())
+            .min(width.saturating_sub(3));
+        draw(&mut out, &lines, cursor_col)?;
+
+        let Some(key) = read_key() else {
+            break None;
+        };
+        match key {
+            Key::Char(c) => {
+                search.query.push(c);
+                search.selected = 0;
+            }
+            Key::Backspace => {
+                search.query.pop();
+                search.selected = 0;
+            }
+            Key::ClearQuery => {
+                search.query.clear();
+                search.selected = 0;
+            }
+            Key::Older => search.selected = (search.selected + 1).min(count.saturating_sub(1)),
+            Key::Newer => search.selected = search.selected.saturating_sub(1),
+            Key::ToggleMode => {
+                search.mode = search.mode.next();
+                search.selected = 0;
+            }
+            Key::ToggleCwd => {
+                search.cwd_only = !search.cwd_only;
+                search.selected = 0;
+            }
+            Key::ToggleExit => {
+                search.exit_filter = search.exit_filter.next();
+                search.selected = 0;
+            }
+            Key::Accept => {
+                break matches
+                    .ok()
+                    .and_then(|matches| matches.get(search.selected).cloned());
+            }
+            Key::Cancel => break None,
+            Key::Other => {}
+        }
+    };
+    // 清除搜索区域并回到输入行，由 rustyline 重新绘制
+    out.write_all(b"\r\x1b[J\x1b[A")?;
+    out.flush()?;
+    Ok(result)
+}
+
+pub struct HistorySearchHandler;
+
+impl ConditionalEventHandler for HistorySearchHandler {
+    fn handle(
+        &self,
+        _evt: &Event,
+        _n: RepeatCount,
+        _positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        // 非交互模式下使用 rustyline 默认的搜索
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let entries = SEARCH_HISTORY.lock().ok()?.clone();
+        let mut search = HistorySearch::new(ctx.line());
+        match run_search(&mut search, &entries) {
+            // 放到输入行中，由用户编辑后再执行
+            Ok(Some(line)) => Some(Cmd::Replace(Movement::WholeBuffer, Some(line))),
+            _ => Some(Cmd::Repaint),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::EntryMetadata;
+
+    use super::*;
+
+    fn entry(line: &str, cwd: Option<&str>, exit_code: Option<i32>) -> HistoryEntry {
+        HistoryEntry {
+            line: line.to_string(),
+            metadata: EntryMetadata {
+                cwd: cwd.map(str::to_string),
+                exit_code,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn entries() -> Vec<HistoryEntry> {
+        vec![
+            entry("git status", None, None),
+            entry("cargo build", Some("/src"), Some(101)),
+            entry("git commit -m fix", Some("/src"), Some(0)),
+            entry("cargo test", Some("/tmp"), Some(0)),
+            entry("git status", Some("/tmp"), Some(0)),
+        ]
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let mut search = HistorySearch::new("git");
+        assert_eq!(
+            search.search(&entries(), "/src").unwrap(),
+            vec!["git status", "git commit -m fix"]
+        );
+
+        search.query = "Cargo".to_string();
+        assert!(search.search(&entries(), "/src").unwrap().is_empty());
+
+        search.query = "cb".to_string();
+        search.mode = SearchMode::Fuzzy;
+        assert_eq!(
+            search.search(&entries(), "/src").unwrap(),
+            vec!["cargo build"]
+        );
+
+        search.query = "^cargo (build|test)$".to_string();
+        search.mode = SearchMode::Regex;
+        assert_eq!(
+            search.search(&entries(), "/src").unwrap(),
+            vec!["cargo test", "cargo build"]
+        );
+
+        search.query = "(".to_string();
+        assert!(search.search(&entries(), "/src").is_err());
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let mut search = HistorySearch::new("");
+        search.cwd_only = true;
+        assert_eq!(
+            search.search(&entries(), "/src").unwrap(),
+            vec!["git commit -m fix", "cargo build"]
+        );
+
+        search.cwd_only = false;
+        search.exit_filter = ExitFilter::Failure;
+        assert_eq!(
+            search.search(&entries(), "/src").unwrap(),
+            vec!["cargo build"]
+        );
+
+        search.exit_filter = ExitFilter::Success;
+        assert_eq!(
+            search.search(&entries(), "/src").unwrap(),
+            vec!["git status", "cargo test", "git commit -m fix"]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut search = HistorySearch::new("git");
+        search.selected = 1;
+        search.cwd_only = true;
+        let lines = search.render(&Ok(vec!["git a".to_string(), "git b".to_string()]), 80);
+        assert_eq!(lines[0], "search (substring, cwd): git");
+        assert_eq!(lines[1], "  git a");
+        assert_eq!(lines[2], "\x1b[7m> git b\x1b[0m");
+        assert_eq!(lines.len(), 4);
+
+        let lines = search.render(&Ok(vec![]), 80);
+        assert_eq!(lines[1], "  (no matches)");
+    }
+}
@@ -1,23 +1,51 @@
-use std::{sync::Mutex, thread};
+use std::{
+    io::Write,
+    sync::{Mutex, atomic::Ordering},
+    thread,
+    time::Instant,
+};
 
 use lazy_static::lazy_static;
-use rustyline::{CompletionType, Config, EditMode, Editor, history::FileHistory};
+use rustyline::{
+    CompletionType, Config, EditMode, Editor, EventHandler, KeyEvent, config::Configurer,
+    history::FileHistory,
+};
 
 use crate::{
-    command::Execute,
+    command::{Execute, LAST_EXIT_CODE},
+    command_index::refresh_support_commands,
+    completer::CompletionMode,
     helper::ShellHelper,
-    history::{CURRENT_SESSION_HISTORY, load_history, save_history},
-    parser::{CommandExecution, parse_tokens},
+    history::{
+        CURRENT_LINE_ADDED, EntryMetadata, HistoryEntry, HistoryMode, LAST_READ_INDEX,
+        append_session_history, finish_last_entry, history_entries, load_history,
+        merge_shared_history, remember_command,
+    },
+    history_expansion::{ExpandedLine, expand_history},
+    history_search::{HistorySearchHandler, SEARCH_HISTORY},
+    parser::{CommandExecution, parse_command, parse_tokens},
+    prompt::render_prompt,
     tokenize::tokenize,
 };
 
+mod arithmetic;
+mod brace;
 mod builtin;
 mod command;
+mod command_index;
 mod completer;
+mod directory;
 mod executable;
+mod git;
+mod help_completion;
 mod helper;
+mod highlighter;
+mod hinter;
 mod history;
+mod history_expansion;
+mod history_search;
 mod parser;
+mod prompt;
 mod redirect;
 mod tokenize;
 #[macro_use]
@@ -27,64 +55,154 @@ mod validator;
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Result<T> = std::result::Result<T, Error>;
 
-static PROMPT: &str = "$ ";
 lazy_static! {
-    static ref HISTORY_FILE: String = std::env::var("HISTFILE").unwrap_or(".history".to_string());
+    // 转为绝对路径，避免 cd 之后写到其他目录
+    static ref HISTORY_FILE: String = {
+        let file = std::env::var("HISTFILE").unwrap_or(".history".to_string());
+        std::path::absolute(&file).map_or(file, |path| path.to_string_lossy().to_string())
+    };
 }
 
 lazy_static! {
     pub static ref RL: Mutex<Editor<ShellHelper, FileHistory>> = {
         let helper = ShellHelper::new();
         let config = Config::builder()
-            .history_ignore_space(true)
-            .auto_add_history(true)
+            // 由 HISTCONTROL 决定忽略哪些命令
+            .history_ignore_space(false)
+            .history_ignore_dups(false)
+            .expect("Failed to configure history")
+            .auto_add_history(false)
             .edit_mode(EditMode::Emacs)
             .completion_type(CompletionType::List)
             .build();
         let mut rl = Editor::with_config(config).expect("Failed to build Editor");
         rl.set_helper(Some(helper));
+        rl.bind_sequence(
+            KeyEvent::ctrl('R'),
+            EventHandler::Conditional(Box::new(HistorySearchHandler)),
+        );
         // let _ = rl.load_history(HISTORY_FILE.as_str());
         Mutex::new(rl)
     };
 }
 
+// 历史展开，展开后的命令会回显出来，展开失败时返回 None
+fn expand_line(line: String) -> Option<(String, bool)> {
+    let history: Vec<String> = RL.lock().unwrap().history().iter().cloned().collect();
+    match expand_history(&line, &history) {
+        Ok(None) => Some((line, false)),
+        Ok(Some(ExpandedLine { line, print_only })) => {
+            eprintln!("{}", line);
+            Some((line, print_only))
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        }
+    }
+}
+
+// 解析并执行一行命令
+fn run_line(line: &str) {
+    match tokenize(line).and_then(|tokens| parse_tokens(&tokens)) {
+        Ok(command_exec_vec) => {
+            for CommandExecution {
+                args,
+                reader,
+                output_writer,
+                error_writer,
+                use_pipe,
+                condition,
+            } in command_exec_vec
+            {
+                if !condition.is_satisfied(LAST_EXIT_CODE.load(Ordering::Relaxed)) {
+                    continue;
+                }
+                // 错误输出要先打开，后面展开和解析的错误才能写进去
+                let mut error_writer = match error_writer.open() {
+                    Ok(error_writer) => error_writer,
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        if use_pipe {
+                            LAST_EXIT_CODE.store(2, Ordering::Relaxed);
+                        }
+                        continue;
+                    }
+                };
+                let (command, output_writer) = match parse_command(&args, output_writer) {
+                    Ok(parsed) => parsed,
+                    Err(err) => {
+                        let _ = writeln!(error_writer, "{}", err);
+                        if use_pipe {
+                            LAST_EXIT_CODE.store(2, Ordering::Relaxed);
+                        }
+                        continue;
+                    }
+                };
+                //? 对于 pipe 采用并行运行是否是正确的做法？
+                if use_pipe {
+                    let exit_code = command.execute(reader, output_writer, error_writer);
+                    LAST_EXIT_CODE.store(exit_code, Ordering::Relaxed);
+                } else {
+                    // 不需要单独 join，因为最后一个 pipe 命令是阻塞执行的，所以在不被取消的情况下，会一直等待前面的命令全部执行完才终止
+                    thread::spawn(move || command.execute(reader, output_writer, error_writer));
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            LAST_EXIT_CODE.store(2, Ordering::Relaxed);
+        }
+    }
+}
+
 fn main() {
     utils::config_logger();
 
-    load_history(HISTORY_FILE.as_str()).ok();
+    if let Ok(count) = load_history(HISTORY_FILE.as_str()) {
+        LAST_READ_INDEX.store(count, Ordering::Relaxed);
+    }
 
     loop {
-        let line = RL.lock().unwrap().readline(PROMPT);
+        let history_mode = HistoryMode::current();
+        if history_mode == HistoryMode::Share {
+            merge_shared_history(HISTORY_FILE.as_str()).ok();
+        }
+        *SEARCH_HISTORY.lock().unwrap() = history_entries();
+        // 高亮时用命令索引判断命令是否存在，只会重新扫描发生变化的目录
+        refresh_support_commands();
+        let line = {
+            let mut rl = RL.lock().unwrap();
+            rl.set_completion_type(CompletionMode::current().completion_type());
+            rl.readline(&render_prompt())
+        };
         match line {
             Ok(line) => {
-                CURRENT_SESSION_HISTORY
-                    .lock()
-                    .expect("Failed to get current session history")
-                    .push(line.clone());
+                // 等待输入期间其他会话可能写入了新的记录，在当前命令之前合并
+                if history_mode == HistoryMode::Share {
+                    merge_shared_history(HISTORY_FILE.as_str()).ok();
+                }
+                let Some((line, print_only)) = expand_line(line) else {
+                    continue;
+                };
+                // 保存到历史中的是展开后的命令
+                let entry = HistoryEntry {
+                    line: line.clone(),
+                    metadata: EntryMetadata::now(),
+                };
+                CURRENT_LINE_ADDED.store(remember_command(entry), Ordering::Relaxed);
+                if print_only {
+                    continue;
+                }
 
-                let tokens = tokenize(&line);
-                match parse_tokens(&tokens) {
-                    Ok(command_exec_vec) => {
-                        for CommandExecution {
-                            command,
-                            reader,
-                            output_writer,
-                            error_writer,
-                            use_pipe,
-                        } in command_exec_vec
-                        {
-                            //? 对于 pipe 采用并行运行是否是正确的做法？
-                            if use_pipe {
-                                command.execute(reader, output_writer, error_writer);
-                            } else {
-                                // 不需要单独 join，因为最后一个 pipe 命令是阻塞执行的，所以在不被取消的情况下，会一直等待前面的命令全部执行完才终止
-                                thread::spawn(move || {
-                                    command.execute(reader, output_writer, error_writer)
-                                });
-                            }
-                        }
-                    }
-                    Err(err) => eprintln!("{}", err),
+                let start = Instant::now();
+                run_line(&line);
+                // history -s 和 fc 会把这条命令从历史中替换掉
+                if CURRENT_LINE_ADDED.load(Ordering::Relaxed) {
+                    finish_last_entry(start.elapsed(), LAST_EXIT_CODE.load(Ordering::Relaxed));
+                }
+                if history_mode != HistoryMode::Exit {
+                    append_session_history(HISTORY_FILE.as_str()).ok();
                 }
             }
             Err(err) => {
@@ -98,5 +216,5 @@ fn main() {
     //     .unwrap()
     //     .append_history(HISTORY_FILE.as_str())
     //     .ok();
-    save_history(HISTORY_FILE.as_str(), true).ok();
+    append_session_history(HISTORY_FILE.as_str()).ok();
 }
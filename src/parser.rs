@@ -5,7 +5,9 @@ use regex::Regex;
 
 use crate::{
     Result,
-    command::{Command, Parse},
+    arithmetic::expand_arithmetic,
+    builtin::ExitCode,
+    command::{Args, Command, Parse},
     redirect::{Reader, Writer},
 };
 
@@ -14,29 +16,78 @@ lazy_static! {
         HashSet::from(["&", "&&", "|", "||", ";"]);
 }
 
+// 根据上一条命令 (pipeline) 的退出码决定是否执行，对应 && 和 ||
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Always,
+    Success,
+    Failure,
+}
+
+impl Condition {
+    pub fn is_satisfied(&self, last_exit_code: ExitCode) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Success => last_exit_code == 0,
+            Condition::Failure => last_exit_code != 0,
+        }
+    }
+}
+
+// 重定向的文件名中可能有 $((expr))，需要在执行前展开，所以到那时才打开文件
+#[derive(Debug)]
+pub enum RedirectTarget {
+    Writer(Writer),
+    File { path: String, append: bool },
+}
+
+impl RedirectTarget {
+    pub fn open(self) -> Result<Writer> {
+        match self {
+            RedirectTarget::Writer(writer) => Ok(writer),
+            RedirectTarget::File { path, append } => Ok(fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(append)
+                .open(expand_arithmetic(&path)?)?
+                .into()),
+        }
+    }
+}
+
+impl<T: Into<Writer>> From<T> for RedirectTarget {
+    fn from(writer: T) -> Self {
+        RedirectTarget::Writer(writer.into())
+    }
+}
+
+// 命令在执行前才展开和解析，所以这里保存的是原始的参数
 #[derive(Debug)]
 pub struct CommandExecution {
-    pub command: Command,
+    pub args: Args,
     pub reader: Reader,
-    pub output_writer: Writer,
-    pub error_writer: Writer,
+    pub output_writer: RedirectTarget,
+    pub error_writer: RedirectTarget,
     pub use_pipe: bool,
+    pub condition: Condition,
 }
 
 impl CommandExecution {
     pub fn new(
-        command: Command,
+        args: Args,
         reader: Reader,
-        output_writer: Writer,
-        error_writer: Writer,
+        output_writer: RedirectTarget,
+        error_writer: RedirectTarget,
         use_pipe: bool,
+        condition: Condition,
     ) -> Self {
         Self {
-            command,
+            args,
             reader,
             output_writer,
             error_writer,
             use_pipe,
+            condition,
         }
     }
 }
@@ -44,11 +95,12 @@ impl CommandExecution {
 impl Default for CommandExecution {
     fn default() -> Self {
         Self {
-            command: Command::Empty,
+            args: vec![],
             reader: Reader::Stdin,
             output_writer: io::stdout().into(),
             error_writer: io::stderr().into(),
             use_pipe: true,
+            condition: Condition::Always,
         }
     }
 }
@@ -73,7 +125,7 @@ fn extract_redirect(s: &str) -> Option<(&str, &str, &str)> {
 fn parse_redirect(
     tokens: &[String],
     start_pos: usize,
-) -> Result<Option<(RedirectIO, Writer, usize)>> {
+) -> Result<Option<(RedirectIO, RedirectTarget, usize)>> {
     //TODO 支持输入重定向
     if let Some((origin, redirect, new)) = extract_redirect(&tokens[start_pos]) {
         let mut num = 1;
@@ -92,12 +144,10 @@ fn parse_redirect(
                     return Err("syntax error".into());
                 } else {
                     num += 1;
-                    fs::OpenOptions::new()
-                        .write(true)
-                        .create(true)
-                        .append(redirect == ">>")
-                        .open(&tokens[start_pos + 1])?
-                        .into()
+                    RedirectTarget::File {
+                        path: tokens[start_pos + 1].clone(),
+                        append: redirect == ">>",
+                    }
                 }
             }
             _ => unreachable!(),
@@ -117,9 +167,37 @@ pub fn parse_tokens(tokens: &[String]) -> Result<Vec<CommandExecution>> {
     let mut next_reader = None;
     let mut output_writer = None;
     let mut error_writer = None;
+    // pipeline 中的所有命令共用同一个执行条件
+    let mut condition = Condition::Always;
+    let mut next_condition = Condition::Always;
 
     while idx < tokens.len() {
-        if let Some((redirect_io, writer, num)) = parse_redirect(tokens, idx)? {
+        if current_cmd_args.is_empty() && tokens[idx] == "[[" {
+            // [[ ]] 内部的 && || < > 都属于条件表达式，不作为操作符和重定向处理
+            let Some(len) = tokens[idx..].iter().position(|token| token == "]]") else {
+                return Err("syntax error: unexpected end of file, expected `]]'".into());
+            };
+            current_cmd_args.extend(tokens[idx..=idx + len].iter().cloned());
+            idx += len + 1;
+        } else if current_cmd_args.is_empty() && tokens[idx].starts_with("((") {
+            // (( expr )) 中的 < > & | 属于表达式，合并为一个参数
+            let Some(len) = tokens[idx..]
+                .iter()
+                .enumerate()
+                .position(|(offset, token)| {
+                    token.ends_with("))") && (offset > 0 || token.len() >= 4)
+                })
+            else {
+                return Err("syntax error: unexpected end of file, expected `))'".into());
+            };
+            let expr = tokens[idx..=idx + len].join(" ");
+            current_cmd_args.extend([
+                "((".to_string(),
+                expr[2..expr.len() - 2].to_string(),
+                "))".to_string(),
+            ]);
+            idx += len + 1;
+        } else if let Some((redirect_io, writer, num)) = parse_redirect(tokens, idx)? {
             match redirect_io {
                 RedirectIO::Stdout => output_writer = Some(writer),
                 RedirectIO::Stderr => error_writer = Some(writer),
@@ -131,23 +209,38 @@ pub fn parse_tokens(tokens: &[String]) -> Result<Vec<CommandExecution>> {
                 "|" => {
                     let (pipe_reader, pipe_writer) = io::pipe()?;
                     next_reader = Some(Reader::PipeReader(pipe_reader));
-                    output_writer = Some(Writer::PipeWriter(pipe_writer));
+                    output_writer = Some(Writer::PipeWriter(pipe_writer).into());
                     false
                 }
-                //TODO 处理 exit code
-                "&&" => todo!(),
-                "||" => todo!(),
-                ";" => true,
+                "&&" => {
+                    next_condition = Condition::Success;
+                    true
+                }
+                "||" => {
+                    next_condition = Condition::Failure;
+                    true
+                }
+                ";" => {
+                    next_condition = Condition::Always;
+                    true
+                }
                 _ => unreachable!(),
             };
+            if current_cmd_args.is_empty() {
+                return Err(format!("syntax error near unexpected token `{}'", tokens[idx]).into());
+            }
             command_exec_vec.push(CommandExecution::new(
-                Command::parse(&current_cmd_args[0], &current_cmd_args[1..])?,
+                current_cmd_args.clone(),
                 reader.take().unwrap_or(Reader::Stdin),
                 output_writer.take().unwrap_or(io::stdout().into()),
                 error_writer.take().unwrap_or(io::stderr().into()),
                 use_pipe,
+                condition,
             ));
 
+            if use_pipe {
+                condition = next_condition;
+            }
             reader = next_reader.take();
             current_cmd_args.clear();
             idx += 1;
@@ -159,17 +252,41 @@ pub fn parse_tokens(tokens: &[String]) -> Result<Vec<CommandExecution>> {
 
     if !current_cmd_args.is_empty() {
         command_exec_vec.push(CommandExecution::new(
-            Command::parse(&current_cmd_args[0], &current_cmd_args[1..])?,
+            current_cmd_args,
             reader.unwrap_or(Reader::Stdin),
             output_writer.unwrap_or(io::stdout().into()),
             error_writer.unwrap_or(io::stderr().into()),
             true,
+            condition,
         ));
+    } else if tokens
+        .last()
+        .is_some_and(|token| matches!(token.as_str(), "&&" | "||" | "|"))
+    {
+        return Err(format!(
+            "syntax error near unexpected token `{}'",
+            tokens[tokens.len() - 1]
+        )
+        .into());
     }
 
     Ok(command_exec_vec)
 }
 
+// 执行前展开 $((expr))、打开输出重定向的文件并解析命令，这样才能读到前面命令的赋值，跳过的命令也不会产生副作用
+pub fn parse_command(args: &[String], output_writer: RedirectTarget) -> Result<(Command, Writer)> {
+    let output_writer = output_writer.open()?;
+    let args = args
+        .iter()
+        .map(|arg| expand_arithmetic(arg))
+        .collect::<Result<Args>>()?;
+    let command = match args.split_first() {
+        Some((command, args)) => Command::parse(command, args)?,
+        None => Command::Empty,
+    };
+    Ok((command, output_writer))
+}
+
 #[allow(unused)]
 #[cfg(test)]
 mod tests {
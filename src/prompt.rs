@@ -0,0 +1,83 @@
+use std::{env, fs};
+
+use crate::{
+    directory::{abbreviate_home, logical_cwd},
+    git::git_status,
+};
+
+pub static DEFAULT_PROMPT: &str = "$ ";
+
+pub fn render_prompt() -> String {
+    match env::var("PS1") {
+        Ok(ps1) if !ps1.is_empty() => render_ps1(&ps1),
+        _ => DEFAULT_PROMPT.to_string(),
+    }
+}
+
+// 支持 bash PS1 中常用的转义: \u \h \w \W \$ \n \\，以及 \g 显示 git 分支和状态
+pub fn render_ps1(ps1: &str) -> String {
+    let mut prompt = String::new();
+    let mut chars = ps1.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => prompt.push_str(&env::var("USER").unwrap_or_default()),
+            Some('h') => prompt.push_str(hostname().split('.').next().unwrap_or_default()),
+            Some('H') => prompt.push_str(&hostname()),
+            Some('w') => prompt.push_str(&current_dir(false)),
+            Some('W') => prompt.push_str(&current_dir(true)),
+            Some('$') => prompt.push(if env::var("USER").is_ok_and(|user| user == "root") {
+                '#'
+            } else {
+                '$'
+            }),
+            Some('g') => {
+                if let Ok(cwd) = env::current_dir()
+                    && let Some(status) = git_status(&cwd)
+                {
+                    prompt.push_str(&status.prompt_segment());
+                }
+            }
+            Some('n') => prompt.push('\n'),
+            Some('\\') => prompt.push('\\'),
+            Some(c) => {
+                prompt.push('\\');
+                prompt.push(c);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+    prompt
+}
+
+fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn current_dir(basename_only: bool) -> String {
+    let cwd = logical_cwd();
+    if basename_only && abbreviate_home(&cwd) != "~" {
+        return cwd
+            .file_name()
+            .map_or("/".to_string(), |name| name.to_string_lossy().to_string());
+    }
+    abbreviate_home(&cwd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_ps1() {
+        assert_eq!(render_ps1("$ "), "$ ");
+        assert_eq!(render_ps1("a\\\\b\\nc> "), "a\\b\nc> ");
+        assert_eq!(render_ps1("\\x\\"), "\\x\\");
+    }
+}
@@ -1,37 +1,74 @@
 use std::collections::HashSet;
 
 use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    Result,
+    arithmetic::{ARITHMETIC_END, ARITHMETIC_START},
+    brace::{WordChar, expand_braces},
+    directory::expand_tilde,
+};
 
 lazy_static! {
+    pub static ref ASSIGNMENT_RE: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*=").unwrap();
     static ref SPECIAL_CHARS: HashSet<char> = HashSet::from(['\'', '"', '\\']);
     static ref TOKEN_END_CHARS: HashSet<char> = HashSet::from(['&', '|', ';']);
     static ref COMMAND_END_TOKENS: HashSet<&'static str> =
         HashSet::from(["&", "&&", "|", "||", ";"]);
 }
 
-pub fn tokenize(input: &str) -> Vec<String> {
+pub fn tokenize(input: &str) -> Result<Vec<String>> {
     debug_assert_ne!(input.chars().next_back(), Some('\\'));
 
     let buffer: Vec<char> = input.trim().chars().collect();
 
     let mut current_pos = 0;
     let mut new_token = String::new();
+    // 记录当前单词的每个字符是否被引用，用于花括号展开
+    let mut word_chars: Vec<WordChar> = vec![];
     let mut cmd_vec: Vec<String> = vec![];
+    // [[ ]] 中的 | 和 & 是正则表达式的一部分，比如 [[ $s =~ ^(a|b)$ ]]
+    let mut in_conditional = false;
 
     while current_pos < buffer.len() {
         let c = buffer[current_pos];
 
+        let is_operator = TOKEN_END_CHARS.contains(&c)
+            && !(in_conditional && c != ';' && buffer.get(current_pos + 1) != Some(&c));
+
+        // 操作符总是单独作为一个 token，比如 echo 'a';echo b
+        if is_operator && !new_token.is_empty() {
+            push_word(
+                &mut cmd_vec,
+                &mut new_token,
+                &mut word_chars,
+                in_conditional,
+            )?;
+        }
+
+        // 只有未加引号的部分才进行花括号展开和 ~ 展开
+        let mut quoted = true;
         let (read_state, part_token, num) = match c {
             '\'' => parse_single_quote(&buffer, current_pos),
-            '"' => parse_double_quote(&buffer, current_pos),
+            '"' => parse_double_quote(&buffer, current_pos)?,
+            '$' if is_arithmetic_start(&buffer, current_pos) => {
+                parse_arithmetic(&buffer, current_pos)?
+            }
             '\\' => parse_backslash(&buffer, current_pos, false),
-            //TODO 1. '|' 需要考虑等待下一行的情况
-            //TODO 2. 增加对 "&&" 和 "||" 的支持
-            '&' | ';' | '|' => (ReadStatus::Finish, c.to_string(), 1),
-            _ => parse_native(&buffer, current_pos),
+            //TODO '|' 需要考虑等待下一行的情况
+            '&' | '|' if is_operator && buffer.get(current_pos + 1) == Some(&c) => {
+                (ReadStatus::Finish, String::from_iter([c, c]), 2)
+            }
+            '&' | ';' | '|' if is_operator => (ReadStatus::Finish, c.to_string(), 1),
+            _ => {
+                quoted = false;
+                parse_native(&buffer, current_pos, in_conditional)
+            }
         };
 
         if !part_token.is_empty() {
+            word_chars.extend(part_token.chars().map(|c| (c, quoted)));
             if new_token.is_empty() {
                 new_token = part_token;
             } else {
@@ -42,19 +79,174 @@ pub fn tokenize(input: &str) -> Vec<String> {
         if let ReadStatus::Finish = read_state
             && !new_token.is_empty()
         {
-            cmd_vec.push(new_token.clone());
-            new_token.clear();
+            let is_command_start = cmd_vec
+                .last()
+                .is_none_or(|last| COMMAND_END_TOKENS.contains(last.as_str()));
+            if new_token == "[[" && is_command_start {
+                in_conditional = true;
+            } else if new_token == "]]" {
+                in_conditional = false;
+            }
+            push_word(
+                &mut cmd_vec,
+                &mut new_token,
+                &mut word_chars,
+                in_conditional,
+            )?;
         }
 
         current_pos += num;
     }
 
     if !new_token.is_empty() {
-        cmd_vec.push(new_token.clone());
-        new_token.clear();
+        push_word(
+            &mut cmd_vec,
+            &mut new_token,
+            &mut word_chars,
+            in_conditional,
+        )?;
     }
 
-    cmd_vec
+    Ok(cmd_vec)
+}
+
+// 先进行花括号展开，一个单词可能展开成多个 token，然后对每个单词进行 ~ 展开。
+// [[ ]] 中和赋值语句不进行花括号展开
+fn push_word(
+    cmd_vec: &mut Vec<String>,
+    new_token: &mut String,
+    word_chars: &mut Vec<WordChar>,
+    in_conditional: bool,
+) -> Result<()> {
+    let words = if in_conditional
+        || is_assignment(cmd_vec, word_chars)
+        || word_chars.iter().all(|(c, quoted)| *quoted || *c != '{')
+    {
+        vec![word_chars.clone()]
+    } else {
+        // 未加引号的空单词会被丢弃，比如 {,}
+        expand_braces(word_chars)?
+            .into_iter()
+            .filter(|word| !word.is_empty())
+            .collect()
+    };
+
+    for word in words {
+        let word = expand_word_tilde(&word);
+        // [[ ]] 中模式里加了引号的部分按字面匹配，需要转义其中的特殊字符
+        let token = match cmd_vec.last().map(String::as_str) {
+            Some("==" | "=" | "!=") if in_conditional => escape_quoted(&word, |c| {
+                if "*?[]\\".contains(c) {
+                    format!("\\{}", c)
+                } else {
+                    c.to_string()
+                }
+            }),
+            Some("=~") if in_conditional => escape_quoted(&word, |c| regex::escape(&c.to_string())),
+            _ => word.iter().map(|(c, _)| c).collect(),
+        };
+        cmd_vec.push(token);
+    }
+    new_token.clear();
+    word_chars.clear();
+    Ok(())
+}
+
+// 命令开头的 NAME=value 是赋值语句，前面可以有其他赋值语句
+fn is_assignment(cmd_vec: &[String], word: &[WordChar]) -> bool {
+    let text: String = word.iter().map(|(c, _)| c).collect();
+    ASSIGNMENT_RE
+        .find(&text)
+        .is_some_and(|name| word[..name.end()].iter().all(|(_, quoted)| !quoted))
+        && cmd_vec
+            .iter()
+            .rev()
+            .take_while(|token| !COMMAND_END_TOKENS.contains(token.as_str()))
+            .all(|token| ASSIGNMENT_RE.is_match(token))
+}
+
+fn escape_quoted(word_chars: &[WordChar], escape: fn(char) -> String) -> String {
+    word_chars
+        .iter()
+        .map(|&(c, quoted)| if quoted { escape(c) } else { c.to_string() })
+        .collect()
+}
+
+fn is_arithmetic_start(buffer: &[char], pos: usize) -> bool {
+    buffer[pos..].starts_with(&['$', '(', '('])
+}
+
+// $((expr))，返回标记后的表达式，执行前由 expand_arithmetic 求值
+fn parse_arithmetic(buffer: &[char], start_pos: usize) -> Result<(ReadStatus, String, usize)> {
+    let mut depth = 0;
+    let mut end_pos = start_pos + 3;
+    while end_pos < buffer.len() {
+        match buffer[end_pos] {
+            '(' => depth += 1,
+            ')' if depth == 0 && buffer.get(end_pos + 1) == Some(&')') => break,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        end_pos += 1;
+    }
+    if end_pos >= buffer.len() {
+        return Err("unexpected EOF while looking for matching `))'".into());
+    }
+
+    let value: String = [ARITHMETIC_START]
+        .into_iter()
+        .chain(buffer[start_pos + 3..end_pos].iter().copied())
+        .chain([ARITHMETIC_END])
+        .collect();
+    let num = end_pos + 2 - start_pos;
+    if end_pos + 2 >= buffer.len() || buffer[end_pos + 2].is_whitespace() {
+        Ok((ReadStatus::Finish, value, num))
+    } else {
+        Ok((ReadStatus::Continue, value, num))
+    }
+}
+
+// 未加引号的 ~ 开头，直到第一个未加引号的 / 为止都没有引号时才展开，比如 ~"user" 不展开。
+// 展开的结果视为加了引号
+fn expand_word_tilde(word: &[WordChar]) -> Vec<WordChar> {
+    let expand = |segment: &[WordChar]| -> Vec<WordChar> {
+        let prefix_len = segment
+            .iter()
+            .position(|&(c, quoted)| c == '/' && !quoted)
+            .unwrap_or(segment.len());
+        let text: String = segment.iter().map(|(c, _)| c).collect();
+        if segment.first() == Some(&('~', false))
+            && segment[..prefix_len].iter().all(|(_, quoted)| !quoted)
+            && let Some(expanded) = expand_tilde(&text)
+        {
+            let rest_len = segment.len() - prefix_len;
+            let prefix_end = expanded.chars().count() - rest_len;
+            return expanded
+                .chars()
+                .take(prefix_end)
+                .map(|c| (c, true))
+                .chain(segment[prefix_len..].iter().cloned())
+                .collect();
+        }
+        segment.to_vec()
+    };
+
+    // 赋值语句中 = 和 : 之后的 ~ 也需要展开，比如 PATH=~/bin:~/.local/bin
+    let text: String = word.iter().map(|(c, _)| c).collect();
+    let Some(name) = ASSIGNMENT_RE.find(&text) else {
+        return expand(word);
+    };
+    let mut expanded = word[..name.end()].to_vec();
+    for (idx, segment) in word[name.end()..]
+        .split(|&(c, quoted)| c == ':' && !quoted)
+        .enumerate()
+    {
+        if idx > 0 {
+            expanded.push((':', false));
+        }
+        expanded.extend(expand(segment));
+    }
+    expanded
 }
 
 enum ReadStatus {
@@ -64,7 +256,11 @@ enum ReadStatus {
 
 //TODO 考虑将 buffer 类型修改为 &str
 
-fn parse_native(buffer: &[char], start_pos: usize) -> (ReadStatus, String, usize) {
+fn parse_native(
+    buffer: &[char],
+    start_pos: usize,
+    in_conditional: bool,
+) -> (ReadStatus, String, usize) {
     let mut token_start_pos = start_pos;
     while token_start_pos < buffer.len() && buffer[token_start_pos].is_whitespace() {
         token_start_pos += 1;
@@ -74,24 +270,28 @@ fn parse_native(buffer: &[char], start_pos: usize) -> (ReadStatus, String, usize
     while token_end_pos < buffer.len()
         && !buffer[token_end_pos].is_whitespace()
         && !SPECIAL_CHARS.contains(&buffer[token_end_pos])
-        && !TOKEN_END_CHARS.contains(&buffer[token_end_pos])
+        && !is_arithmetic_start(buffer, token_end_pos)
+        && !(TOKEN_END_CHARS.contains(&buffer[token_end_pos])
+            && !(in_conditional && buffer[token_end_pos] != ';'))
     {
         token_end_pos += 1;
     }
 
     let token = buffer[token_start_pos..token_end_pos].iter().collect();
 
-    let (read_state, num) =
-        if token_end_pos < buffer.len() && SPECIAL_CHARS.contains(&buffer[token_end_pos]) {
-            (ReadStatus::Continue, token_end_pos - start_pos)
-        } else {
-            let mut end_pos = token_end_pos;
-            while end_pos < buffer.len() && buffer[end_pos].is_whitespace() {
-                end_pos += 1;
-            }
+    let (read_state, num) = if token_end_pos < buffer.len()
+        && (SPECIAL_CHARS.contains(&buffer[token_end_pos])
+            || is_arithmetic_start(buffer, token_end_pos))
+    {
+        (ReadStatus::Continue, token_end_pos - start_pos)
+    } else {
+        let mut end_pos = token_end_pos;
+        while end_pos < buffer.len() && buffer[end_pos].is_whitespace() {
+            end_pos += 1;
+        }
 
-            (ReadStatus::Finish, end_pos - start_pos)
-        };
+        (ReadStatus::Finish, end_pos - start_pos)
+    };
 
     (read_state, token, num)
 }
@@ -121,7 +321,7 @@ fn parse_single_quote(buffer: &[char], start_pos: usize) -> (ReadStatus, String,
     }
 }
 
-fn parse_double_quote(buffer: &[char], start_pos: usize) -> (ReadStatus, String, usize) {
+fn parse_double_quote(buffer: &[char], start_pos: usize) -> Result<(ReadStatus, String, usize)> {
     debug_assert_ne!(start_pos + 1, buffer.len());
 
     let mut token = String::new();
@@ -131,6 +331,10 @@ fn parse_double_quote(buffer: &[char], start_pos: usize) -> (ReadStatus, String,
             let (_, part_token, num) = parse_backslash(buffer, end_pos, true);
             token.push_str(&part_token);
             end_pos += num;
+        } else if is_arithmetic_start(buffer, end_pos) {
+            let (_, part_token, num) = parse_arithmetic(buffer, end_pos)?;
+            token.push_str(&part_token);
+            end_pos += num;
         } else {
             token.push(buffer[end_pos]);
             end_pos += 1;
@@ -143,13 +347,13 @@ fn parse_double_quote(buffer: &[char], start_pos: usize) -> (ReadStatus, String,
         let num = end_pos - start_pos + 1; // +1 是跳过最后的 "
         if end_pos + 1 >= buffer.len() || buffer[end_pos + 1].is_whitespace() {
             // 已经到 buffer 末尾，或者 " 的下一个字符是空白字符，那么当前 token 已结束
-            (ReadStatus::Finish, token, num)
+            Ok((ReadStatus::Finish, token, num))
         } else {
-            (ReadStatus::Continue, token, num)
+            Ok((ReadStatus::Continue, token, num))
         }
     } else {
         // " 未匹配上，但 buffer 已结束
-        (ReadStatus::Continue, token, end_pos - start_pos)
+        Ok((ReadStatus::Continue, token, end_pos - start_pos))
     }
 }
 
@@ -193,6 +397,10 @@ mod tests {
 
     use super::*;
 
+    fn tokenize(input: &str) -> Vec<String> {
+        super::tokenize(input).unwrap()
+    }
+
     #[test]
     fn test_parse_native() {
         assert_eq!(
@@ -293,4 +501,126 @@ mod tests {
             vec_str_to_vec_string::<Vec<_>>(&["cat", "/tmp/file\\name", "/tmp/file\\ name"])
         );
     }
+
+    #[test]
+    fn test_parse_operators() {
+        assert_eq!(
+            tokenize("true&&echo a || echo b"),
+            vec_str_to_vec_string::<Vec<_>>(&["true", "&&", "echo", "a", "||", "echo", "b"])
+        );
+        assert_eq!(
+            tokenize("echo 'a';echo b | cat"),
+            vec_str_to_vec_string::<Vec<_>>(&["echo", "a", ";", "echo", "b", "|", "cat"])
+        );
+    }
+
+    #[test]
+    fn test_expand_tilde() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(
+            tokenize("ls ~ ~/src a~ '~' \"~/x\" \\~"),
+            vec![
+                "ls".to_string(),
+                home.clone(),
+                format!("{}/src", home),
+                "a~".to_string(),
+                "~".to_string(),
+                "~/x".to_string(),
+                "~".to_string(),
+            ]
+        );
+        assert_eq!(
+            tokenize("PATH=~/bin:~root:/usr/bin"),
+            vec![format!("PATH={}/bin:/root:/usr/bin", home)]
+        );
+        assert_eq!(
+            tokenize("cd ~root ~\"root\" ~no_such_user_123"),
+            vec_str_to_vec_string::<Vec<_>>(&["cd", "/root", "~root", "~no_such_user_123"])
+        );
+    }
+
+    #[test]
+    fn test_parse_conditional() {
+        assert_eq!(
+            tokenize("[[ $s =~ ^(a|b)&c$ && -n x ]] && echo y|cat"),
+            vec_str_to_vec_string::<Vec<_>>(&[
+                "[[",
+                "$s",
+                "=~",
+                "^(a|b)&c$",
+                "&&",
+                "-n",
+                "x",
+                "]]",
+                "&&",
+                "echo",
+                "y",
+                "|",
+                "cat"
+            ])
+        );
+        assert_eq!(
+            tokenize("[[ \"$x\" == \"a*\"b* && $y =~ 'a.'. ]]"),
+            vec_str_to_vec_string::<Vec<_>>(&[
+                "[[", "$x", "==", "a\\*b*", "&&", "$y", "=~", "a\\..", "]]"
+            ])
+        );
+        assert_eq!(
+            tokenize("[[ $x == \\? ]]"),
+            vec_str_to_vec_string::<Vec<_>>(&["[[", "$x", "==", "\\?", "]]"])
+        );
+    }
+
+    #[test]
+    fn test_parse_arithmetic() {
+        assert_eq!(
+            tokenize("echo $((1 + 2)) a$(( (1 + 2) * 3 ))b \"$((2 ** 4))\" '$((1))'"),
+            vec_str_to_vec_string::<Vec<_>>(&[
+                "echo",
+                "\u{1}1 + 2\u{2}",
+                "a\u{1} (1 + 2) * 3 \u{2}b",
+                "\u{1}2 ** 4\u{2}",
+                "$((1))"
+            ])
+        );
+        assert!(super::tokenize("echo $((1 / 0))").is_ok());
+        assert!(super::tokenize("echo $((1 + 2)").is_err());
+    }
+
+    #[test]
+    fn test_brace_expansion() {
+        assert_eq!(
+            tokenize("mkdir -p src/{bin,lib}"),
+            vec_str_to_vec_string::<Vec<_>>(&["mkdir", "-p", "src/bin", "src/lib"])
+        );
+        assert_eq!(
+            tokenize("echo \"a b\"{1..2} '{a,b}' \\{c,d} x{\",\"}"),
+            vec_str_to_vec_string::<Vec<_>>(&["echo", "a b1", "a b2", "{a,b}", "{c,d}", "x{,}"])
+        );
+        assert_eq!(
+            tokenize("echo {,} {a,b}&&echo ${HOME%/}"),
+            vec_str_to_vec_string::<Vec<_>>(&["echo", "a", "b", "&&", "echo", "${HOME%/}"])
+        );
+        assert_eq!(
+            tokenize("echo {~,/tmp}/x a={1,2} && b={1,2} c={3,4} echo d={5,6}"),
+            vec_str_to_vec_string::<Vec<_>>(&[
+                "echo",
+                &format!("{}/x", std::env::var("HOME").unwrap()),
+                "/tmp/x",
+                "a=1",
+                "a=2",
+                "&&",
+                "b={1,2}",
+                "c={3,4}",
+                "echo",
+                "d=5",
+                "d=6"
+            ])
+        );
+        assert!(super::tokenize("echo {1..9999999999}").is_err());
+        assert_eq!(
+            tokenize("[[ {a,b} == x ]]"),
+            vec_str_to_vec_string::<Vec<_>>(&["[[", "{a,b}", "==", "x", "]]"])
+        );
+    }
 }
@@ -1,9 +1,13 @@
 use std::{
     fs,
+    io::Read,
     path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
 };
 
 use is_executable::IsExecutable;
+use regex::Regex;
 
 pub fn config_logger() {
     let subscriber = tracing_subscriber::fmt()
@@ -18,6 +22,11 @@ pub fn config_logger() {
     tracing::subscriber::set_global_default(subscriber).expect("Failed to set global subscriber");
 }
 
+// 只有主线程可以修改环境变量和 shell 的状态，管道中的其他命令运行在单独的线程中
+pub fn is_main_thread() -> bool {
+    thread::current().name() == Some("main")
+}
+
 #[allow(unused)]
 pub fn set_env_path() {
     unsafe { std::env::set_var("PATH", "/usr/bin:/usr/local/bin:$PATH") };
@@ -43,6 +52,161 @@ pub fn get_executables_from_dir(dir: &Path) -> Vec<PathBuf> {
     }
 }
 
+// Damerau-Levenshtein 距离 (optimal string alignment)，相邻字符交换算作一次编辑
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut dp = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dp.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    dp[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            dp[i][j] = (dp[i - 1][j] + 1)
+                .min(dp[i][j - 1] + 1)
+                .min(dp[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                dp[i][j] = dp[i][j].min(dp[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    dp[a.len()][b.len()]
+}
+
+// shell 通配符: * ? [...] [!...]，\ 转义下一个字符
+pub fn glob_to_regex(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = "^".to_string();
+    let mut idx = 0;
+    while idx < chars.len() {
+        match chars[idx] {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '\\' if idx + 1 < chars.len() => {
+                idx += 1;
+                regex.push_str(&regex::escape(&chars[idx].to_string()));
+            }
+            '[' => {
+                // 找不到匹配的 ] 时按普通字符处理
+                let mut end = idx + 1;
+                if matches!(chars.get(end), Some('!' | '^')) {
+                    end += 1;
+                }
+                if chars.get(end) == Some(&']') {
+                    end += 1;
+                }
+                while end < chars.len() && chars[end] != ']' {
+                    end += 1;
+                }
+                if end >= chars.len() {
+                    regex.push_str("\\[");
+                } else {
+                    let mut class = "[".to_string();
+                    let mut start = idx + 1;
+                    if matches!(chars[start], '!' | '^') {
+                        class.push('^');
+                        start += 1;
+                    }
+                    for &c in &chars[start..end] {
+                        if matches!(c, '\\' | '[' | '&' | '~') {
+                            class.push('\\');
+                        }
+                        class.push(c);
+                    }
+                    class.push(']');
+                    regex.push_str(&class);
+                    idx = end;
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        idx += 1;
+    }
+    regex.push('$');
+    regex
+}
+
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    Regex::new(&glob_to_regex(pattern)).is_ok_and(|re| re.is_match(text))
+}
+
+// /etc/passwd 中每一行的格式为 name:password:uid:gid:gecos:home:shell
+fn passwd_entries() -> Vec<(String, PathBuf)> {
+    fs::read_to_string("/etc/passwd")
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (fields.len() >= 6).then(|| (fields[0].to_string(), PathBuf::from(fields[5])))
+        })
+        .collect()
+}
+
+pub fn user_names() -> Vec<String> {
+    passwd_entries().into_iter().map(|(name, _)| name).collect()
+}
+
+pub fn user_home_dir(user: &str) -> Option<PathBuf> {
+    passwd_entries()
+        .into_iter()
+        .find_map(|(name, home)| (name == user).then_some(home))
+}
+
+// 按 strftime 格式化本地时间，用于 HISTTIMEFORMAT
+pub fn format_local_time(format: &str, timestamp: u64) -> String {
+    let Ok(format) = std::ffi::CString::new(format) else {
+        return String::new();
+    };
+    let time = timestamp as libc::time_t;
+    let mut buffer = [0u8; 256];
+    let len = unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return String::new();
+        }
+        libc::strftime(
+            buffer.as_mut_ptr() as *mut libc::c_char,
+            buffer.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+    String::from_utf8_lossy(&buffer[..len]).to_string()
+}
+
+// 用于补全时运行外部命令，超时则直接 kill，避免卡住输入
+pub fn read_command_output(command: &mut process::Command, timeout: Duration) -> Option<String> {
+    let mut child = command
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut output = vec![];
+        stdout.read_to_end(&mut output).ok();
+        String::from_utf8_lossy(&output).to_string()
+    });
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+            _ => {
+                child.kill().ok();
+                child.wait().ok();
+                return None;
+            }
+        }
+    }
+    reader.join().ok()
+}
+
 #[macro_export]
 macro_rules! map_err_to_exit_code {
     ($val:expr) => {